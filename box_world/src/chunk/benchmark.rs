@@ -0,0 +1,114 @@
+use std::{
+    fmt, io,
+    time::{Duration, Instant},
+};
+
+use super::{
+    format::{self, ChunkCodec, ChunkCompression},
+    store::ChunkStore,
+};
+
+/// Zstd levels measured for the codecs that take one
+const BENCHMARK_LEVELS: [i32; 4] = [1, 3, 9, 19];
+
+/// Size and speed of one codec over the sampled chunks
+pub struct CodecReport {
+    pub codec: ChunkCodec,
+    pub level: i32,
+    pub chunks: usize,
+    pub bytes: usize,
+    pub encode_time: Duration,
+    pub decode_time: Duration,
+}
+
+impl CodecReport {
+    pub fn bytes_per_chunk(&self) -> f64 {
+        self.bytes as f64 / self.chunks.max(1) as f64
+    }
+}
+
+impl fmt::Display for CodecReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let per_chunk = |time: Duration| time.as_secs_f64() * 1e6 / self.chunks.max(1) as f64;
+        write!(
+            f,
+            "{:<10} level {:>2}: {:>9.1} bytes/chunk, encode {:>7.1} us/chunk, decode {:>7.1} us/chunk",
+            self.codec.to_string(),
+            self.level,
+            self.bytes_per_chunk(),
+            per_chunk(self.encode_time),
+            per_chunk(self.decode_time)
+        )
+    }
+}
+
+/// Measure every codec on random chunks of a world.
+/// The dictionary is trained on a different set of chunks than the one measured,
+/// so its numbers match what a world gets for chunks saved after training.
+pub fn benchmark_codecs(
+    chunk_store: &dyn ChunkStore,
+    compression: &ChunkCompression,
+    sample_size: usize,
+) -> io::Result<Vec<CodecReport>> {
+    let mut samples = format::sample_chunk_bins(chunk_store, compression, sample_size * 2)?;
+    if samples.len() < 2 {
+        return Err(io::Error::other(
+            "Not enough chunks in the world to benchmark",
+        ));
+    }
+    let measured = samples.split_off(samples.len() / 2);
+    let dictionary = format::train_dictionary(&samples)?;
+
+    let mut reports = vec![measure(
+        &ChunkCompression::new(ChunkCodec::None, 0, None),
+        0,
+        &measured,
+    )?];
+    for level in BENCHMARK_LEVELS {
+        reports.push(measure(
+            &ChunkCompression::new(ChunkCodec::Zstd, level, None),
+            level,
+            &measured,
+        )?);
+        reports.push(measure(
+            &ChunkCompression::new(ChunkCodec::ZstdDict, level, Some(&dictionary)),
+            level,
+            &measured,
+        )?);
+    }
+    Ok(reports)
+}
+
+fn measure(
+    compression: &ChunkCompression,
+    level: i32,
+    samples: &[Vec<u8>],
+) -> io::Result<CodecReport> {
+    let start = Instant::now();
+    let blobs = samples
+        .iter()
+        .map(|raw_chunk_bin| compression.encode_bin(raw_chunk_bin))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| io::Error::other("Failed to encode chunk"))?;
+    let encode_time = start.elapsed();
+
+    let start = Instant::now();
+    for (blob, raw_chunk_bin) in blobs.iter().zip(samples) {
+        let decoded = compression
+            .decode_bin(blob)
+            .map_err(|err| io::Error::other(err.to_string()))?;
+        if &decoded != raw_chunk_bin {
+            return Err(io::Error::other("Chunk changed after a round trip"));
+        }
+    }
+    let decode_time = start.elapsed();
+
+    Ok(CodecReport {
+        codec: compression.codec(),
+        level,
+        chunks: samples.len(),
+        bytes: blobs.iter().map(Vec::len).sum(),
+        encode_time,
+        decode_time,
+    })
+}
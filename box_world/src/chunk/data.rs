@@ -2,25 +2,37 @@ use bevy::prelude::Component;
 use ndshape::{ConstShape, ConstShape3usize};
 use serde::{Deserialize, Serialize};
 
-use crate::voxel::Voxel;
+use std::collections::BTreeMap;
 
-use super::{lighting::LightStorage, storage::Storage};
+use crate::voxel::{ChunkLocalVoxelPos, Voxel};
+
+use super::{
+    lighting::LightStorage,
+    persist::{BlockEntity, SavedEntity},
+    storage::Storage,
+};
 
 const CHUNK_EDGE: usize = 16;
 type ChunkShape = ConstShape3usize<CHUNK_EDGE, CHUNK_EDGE, CHUNK_EDGE>;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RawChunk {
     voxels: Storage,
     lights: LightStorage,
+    block_entities: BTreeMap<u16, BlockEntity>,
+    entities: Vec<SavedEntity>,
 }
 
 #[derive(Component, Clone, Debug)]
 pub struct ChunkData {
     voxels: Storage,
     lights: LightStorage,
+    /// Keyed by the linear index of the voxel
+    block_entities: BTreeMap<u16, BlockEntity>,
     change_count: u16,
-    dirty: bool,
+    /// Bumped on every change, the chunk is dirty until a save of the latest revision lands
+    revision: u32,
+    saved_revision: u32,
 }
 
 impl Default for ChunkData {
@@ -28,8 +40,10 @@ impl Default for ChunkData {
         Self {
             voxels: Storage::new(ChunkShape::USIZE),
             lights: LightStorage::new(),
+            block_entities: BTreeMap::new(),
             change_count: 0,
-            dirty: true,
+            revision: 1,
+            saved_revision: 0,
         }
     }
 }
@@ -41,7 +55,12 @@ impl ChunkData {
     }
 
     pub fn set(&mut self, x: u32, y: u32, z: u32, voxel: Voxel) {
-        self.voxels.set(Self::linearize(x, y, z), voxel);
+        let idx = Self::linearize(x, y, z);
+        // Block entities belong to the voxel they were placed with
+        if self.voxels.get(idx) != voxel {
+            self.block_entities.remove(&(idx as u16));
+        }
+        self.voxels.set(idx, voxel);
         self.change_count += 1;
         self.set_dirty(true);
 
@@ -51,6 +70,21 @@ impl ChunkData {
         }
     }
 
+    pub fn get_block_entity(&self, pos: ChunkLocalVoxelPos) -> Option<&BlockEntity> {
+        self.block_entities
+            .get(&(Self::linearize(pos.x, pos.y, pos.z) as u16))
+    }
+
+    /// Attach data to the voxel at `pos`, or remove it with None
+    pub fn set_block_entity(&mut self, pos: ChunkLocalVoxelPos, block_entity: Option<BlockEntity>) {
+        let idx = Self::linearize(pos.x, pos.y, pos.z) as u16;
+        match block_entity {
+            Some(block_entity) => self.block_entities.insert(idx, block_entity),
+            None => self.block_entities.remove(&idx),
+        };
+        self.set_dirty(true);
+    }
+
     /// Output contains both lights
     pub fn get_light(&self, x: u32, y: u32, z: u32) -> u8 {
         self.lights.get_light(Self::linearize(x, y, z))
@@ -84,15 +118,30 @@ impl ChunkData {
     }
 
     pub fn is_empty(&self) -> bool {
-        self.is_uniform() && self.get(0, 0, 0).is_empty()
+        self.is_uniform() && self.get(0, 0, 0).is_air()
     }
 
     pub fn is_dirty(&self) -> bool {
-        self.dirty
+        self.revision != self.saved_revision
     }
 
     pub fn set_dirty(&mut self, dirty: bool) {
-        self.dirty = dirty;
+        if dirty {
+            self.revision = self.revision.wrapping_add(1);
+        } else {
+            self.saved_revision = self.revision;
+        }
+    }
+
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    /// The given revision was written to disk, later changes keep the chunk dirty
+    pub fn mark_saved(&mut self, revision: u32) {
+        if revision == self.revision {
+            self.saved_revision = revision;
+        }
     }
 
     pub fn trim(&mut self) {
@@ -122,19 +171,25 @@ impl ChunkData {
         (res[0] as u32, res[1] as u32, res[2] as u32)
     }
 
-    pub fn from_raw(raw_chunk: RawChunk) -> Self {
-        Self {
+    /// Split a stored chunk into its data and the entities to spawn in it
+    pub fn from_raw(raw_chunk: RawChunk) -> (Self, Vec<SavedEntity>) {
+        let data = Self {
             voxels: raw_chunk.voxels,
             lights: raw_chunk.lights,
+            block_entities: raw_chunk.block_entities,
             change_count: 0,
-            dirty: false,
-        }
+            revision: 0,
+            saved_revision: 0,
+        };
+        (data, raw_chunk.entities)
     }
 
-    pub fn to_raw(&self) -> RawChunk {
+    pub fn to_raw(&self, entities: Vec<SavedEntity>) -> RawChunk {
         RawChunk {
             voxels: self.voxels.clone(),
             lights: self.lights.clone(),
+            block_entities: self.block_entities.clone(),
+            entities,
         }
     }
 }
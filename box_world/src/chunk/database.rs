@@ -1,42 +1,203 @@
-use std::io::Cursor;
+use std::{io, path::Path, sync::Arc};
 
-use bevy::prelude::info_span;
+use bevy::utils::HashMap;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{params, Connection};
-use zstd::stream::copy_encode;
+use rusqlite::{params, types::Value, Connection, OpenFlags, OptionalExtension};
 
-use super::{data::RawChunk, ChunkPos};
+use super::{
+    data::RawChunk, format::ChunkCompression, pending::PendingWrite, persist::PlayerState,
+    store::ChunkStore, ChunkPos, WorldMeta,
+};
 
+/// Encode and store chunks in a single write
 pub fn save_raw_chunks(
+    chunk_store: &dyn ChunkStore,
+    compression: &ChunkCompression,
+    chunks: &[(ChunkPos, Arc<RawChunk>)],
+) -> io::Result<()> {
+    let blobs = chunks
+        .iter()
+        .map(|(chunk_pos, chunk_data)| {
+            let blob = compression.encode(chunk_data).ok_or_else(|| {
+                io::Error::other(format!("Failed to encode chunk {:?}", chunk_pos))
+            })?;
+            Ok((*chunk_pos, blob))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    chunk_store.save_blobs(blobs)
+}
+
+/// Whether the sqlite chunk table holds any chunk, worlds from before metadata only used this one
+pub fn has_chunks(connection_pool: &Pool<SqliteConnectionManager>) -> bool {
+    let connection = connection_pool.get().unwrap();
+    connection
+        .query_row("SELECT EXISTS(SELECT 1 FROM blocks);", [], |row| row.get(0))
+        .unwrap()
+}
+
+pub fn load_voxel_ids(connection_pool: &Pool<SqliteConnectionManager>) -> HashMap<String, u16> {
+    let connection = connection_pool.get().unwrap();
+    let mut stmt = connection
+        .prepare("SELECT name, id FROM voxel_ids;")
+        .unwrap();
+    let ids = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .filter_map(|row| row.ok())
+        .collect();
+    ids
+}
+
+/// Ids of every voxel type of the world, the names it doesn't know yet get the next free ids.
+/// Air is always id 0 so default chunk storage is empty.
+pub fn reserve_voxel_ids(
     connection_pool: &Pool<SqliteConnectionManager>,
-    chunks: Vec<(ChunkPos, RawChunk)>,
-) {
-    let _span = info_span!("Saving chunks to disk").entered();
+    names: &[&str],
+) -> HashMap<String, u16> {
+    let mut ids = load_voxel_ids(connection_pool);
+    let air_id = *ids.entry("air".to_string()).or_insert(0);
+    assert!(air_id == 0, "Voxel air must have id 0, found {}", air_id);
+
+    let mut next_id = ids.values().max().map_or(0, |id| id + 1);
+    let mut new_names: Vec<&str> = names
+        .iter()
+        .copied()
+        .filter(|name| !ids.contains_key(*name))
+        .collect();
+    new_names.sort();
+    new_names.dedup();
+    for name in new_names {
+        ids.insert(name.to_string(), next_id);
+        next_id += 1;
+    }
 
+    save_voxel_ids(connection_pool, &ids);
+    ids
+}
+
+pub fn save_voxel_ids(connection_pool: &Pool<SqliteConnectionManager>, ids: &HashMap<String, u16>) {
     let connection = connection_pool.get().unwrap();
     connection.execute("BEGIN;", []).unwrap();
-    for (chunk_pos, chunk_data) in chunks.iter() {
-        save_raw_chunk(&connection, chunk_pos, chunk_data);
+    for (name, id) in ids.iter() {
+        connection
+            .execute(
+                "INSERT OR IGNORE INTO voxel_ids (name, id) values (?1, ?2)",
+                params![name, id],
+            )
+            .unwrap();
     }
     connection.execute("COMMIT;", []).unwrap();
 }
 
-fn save_raw_chunk(connection: &Connection, chunk_pos: &ChunkPos, chunk_data: &RawChunk) {
-    if let Ok(raw_chunk_bin) = bincode::serialize(chunk_data) {
-        let mut final_chunk = Cursor::new(raw_chunk_bin);
-        let mut output = Cursor::new(Vec::new());
-        copy_encode(&mut final_chunk, &mut output, 0).unwrap();
+pub fn load_world_meta(connection_pool: &Pool<SqliteConnectionManager>) -> Option<WorldMeta> {
+    let connection = connection_pool.get().unwrap();
+    query_world_meta(&connection).unwrap()
+}
+
+/// Metadata of a world file, opened read-only so the file is left untouched.
+/// Unreadable files and worlds without metadata give `None`.
+pub fn read_world_meta(path: &Path) -> Option<WorldMeta> {
+    let connection = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .ok()?;
+    query_world_meta(&connection).ok().flatten()
+}
+
+fn query_world_meta(connection: &Connection) -> rusqlite::Result<Option<WorldMeta>> {
+    let mut stmt = connection.prepare("SELECT key, value FROM world_meta;")?;
+    let values: HashMap<String, Value> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .filter_map(|row| row.ok())
+        .collect();
+    Ok(WorldMeta::from_values(&values))
+}
+
+pub fn save_world_meta(connection_pool: &Pool<SqliteConnectionManager>, meta: &WorldMeta) {
+    let connection = connection_pool.get().unwrap();
+    connection.execute("BEGIN;", []).unwrap();
+    for (key, value) in meta.to_values() {
         connection
             .execute(
-                "REPLACE INTO blocks (posx, posy, posz, data) values (?1, ?2, ?3, ?4)",
-                params![
-                    chunk_pos.x,
-                    chunk_pos.y,
-                    chunk_pos.z,
-                    &output.get_ref().clone(),
-                ],
+                "REPLACE INTO world_meta (key, value) values (?1, ?2)",
+                params![key, value],
             )
             .unwrap();
     }
+    connection.execute("COMMIT;", []).unwrap();
+}
+
+pub fn load_pending_writes(
+    connection_pool: &Pool<SqliteConnectionManager>,
+) -> HashMap<ChunkPos, Vec<PendingWrite>> {
+    let connection = connection_pool.get().unwrap();
+    let mut stmt = connection
+        .prepare("SELECT posx, posy, posz, data FROM pending_writes;")
+        .unwrap();
+    let writes = stmt
+        .query_map([], |row| {
+            let data: Vec<u8> = row.get(3)?;
+            Ok((ChunkPos::new(row.get(0)?, row.get(1)?, row.get(2)?), data))
+        })
+        .unwrap()
+        .filter_map(|row| row.ok())
+        .filter_map(|(pos, data)| Some((pos, bincode::deserialize(&data).ok()?)))
+        .collect();
+    writes
+}
+
+/// Replace every saved pending write with the given ones
+pub fn save_pending_writes(
+    connection_pool: &Pool<SqliteConnectionManager>,
+    writes: &[(ChunkPos, Vec<PendingWrite>)],
+) -> io::Result<()> {
+    let mut connection = connection_pool.get().map_err(io::Error::other)?;
+    let transaction = connection.transaction().map_err(io::Error::other)?;
+    transaction
+        .execute("DELETE FROM pending_writes;", [])
+        .map_err(io::Error::other)?;
+    for (pos, chunk_writes) in writes.iter() {
+        let data = bincode::serialize(chunk_writes).map_err(io::Error::other)?;
+        transaction
+            .execute(
+                "INSERT INTO pending_writes (posx, posy, posz, data) values (?1, ?2, ?3, ?4)",
+                params![pos.x, pos.y, pos.z, data],
+            )
+            .map_err(io::Error::other)?;
+    }
+    transaction.commit().map_err(io::Error::other)
+}
+
+/// Name of the player of a local game in the `players` table
+const LOCAL_PLAYER: &str = "local";
+
+pub fn load_player(connection_pool: &Pool<SqliteConnectionManager>) -> Option<PlayerState> {
+    let connection = connection_pool.get().unwrap();
+    let data: Vec<u8> = connection
+        .query_row(
+            "SELECT data FROM players WHERE name = ?1;",
+            params![LOCAL_PLAYER],
+            |row| row.get(0),
+        )
+        .optional()
+        .unwrap()?;
+    bincode::deserialize(&data).ok()
+}
+
+pub fn save_player(
+    connection_pool: &Pool<SqliteConnectionManager>,
+    player: &PlayerState,
+) -> io::Result<()> {
+    let data = bincode::serialize(player).map_err(io::Error::other)?;
+    let connection = connection_pool.get().map_err(io::Error::other)?;
+    connection
+        .execute(
+            "REPLACE INTO players (name, data) values (?1, ?2)",
+            params![LOCAL_PLAYER, data],
+        )
+        .map_err(io::Error::other)?;
+    Ok(())
 }
@@ -0,0 +1,422 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    str::FromStr,
+    sync::Arc,
+};
+
+use bevy::prelude::error;
+use rand::seq::SliceRandom;
+use zstd::{
+    dict::{DecoderDictionary, EncoderDictionary},
+    stream::{Decoder, Encoder},
+};
+
+use super::{
+    data::RawChunk,
+    legacy::{self, LegacyVoxelIds},
+    persist::SavedEntity,
+    store::ChunkStore,
+    ChunkData, ChunkPos, WorldMeta,
+};
+
+/// Version of the serialized `RawChunk` written by this build.
+/// Bump it whenever `Storage`, `LightStorage` or `Voxel` change shape and register a migration.
+pub const CHUNK_FORMAT_VERSION: u16 = 4;
+
+/// Marks a blob wrapped in a versioned envelope
+const ENVELOPE_MAGIC: &[u8; 4] = b"BWCK";
+/// Magic, version and, from version 3, the codec
+const ENVELOPE_HEADER_SIZE: usize = ENVELOPE_MAGIC.len() + 3;
+/// Upper bound of a trained dictionary, zstd recommends around 100 times less than the samples
+const DICTIONARY_SIZE: usize = 16 * 1024;
+
+/// Chunks sampled to train a dictionary
+pub const DICTIONARY_SAMPLES: usize = 4096;
+
+/// Every zstd frame starts with these bytes, blobs from before the envelope are bare frames
+const ZSTD_MAGIC: &[u8; 4] = &[0x28, 0xB5, 0x2F, 0xFD];
+
+/// Rewrites a serialized chunk from one format version to the next
+struct Migration {
+    from: u16,
+    migrate: fn(Vec<u8>, &LegacyVoxelIds) -> Result<Vec<u8>, ChunkDecodeError>,
+}
+
+/// Applied in order to bring old chunks up to `CHUNK_FORMAT_VERSION`.
+/// Migrations work on the uncompressed bincode bytes, so they keep their own copies
+/// of the old types instead of depending on the current ones.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 1,
+        migrate: legacy::migrate_voxel_ids,
+    },
+    Migration {
+        from: 2,
+        // Version 3 only records the codec in the envelope
+        migrate: unchanged,
+    },
+    Migration {
+        from: 3,
+        migrate: add_chunk_extras,
+    },
+];
+
+fn unchanged(
+    raw_chunk_bin: Vec<u8>,
+    _voxel_ids: &LegacyVoxelIds,
+) -> Result<Vec<u8>, ChunkDecodeError> {
+    Ok(raw_chunk_bin)
+}
+
+/// Version 4 appends block entities and persisted entities to the chunk.
+/// Both start empty, bincode writes the length of each as a u64.
+fn add_chunk_extras(
+    mut raw_chunk_bin: Vec<u8>,
+    _voxel_ids: &LegacyVoxelIds,
+) -> Result<Vec<u8>, ChunkDecodeError> {
+    raw_chunk_bin.extend_from_slice(&0u64.to_le_bytes());
+    raw_chunk_bin.extend_from_slice(&0u64.to_le_bytes());
+    Ok(raw_chunk_bin)
+}
+
+/// Failure to turn a stored blob back into a chunk
+#[derive(Debug)]
+pub enum ChunkDecodeError {
+    Decompress(io::Error),
+    Deserialize(bincode::Error),
+    /// Written by a newer build, the chunk must not be touched
+    UnsupportedVersion(u16),
+    /// No migration leads out of this version
+    MissingMigration(u16),
+    UnknownEnvelope,
+    /// Compressed with a dictionary the world doesn't have
+    MissingDictionary,
+    /// Version 1 chunk decoded without the voxel ids of the world
+    MissingVoxelIds,
+    /// Version 1 voxel that wasn't part of the first release
+    UnknownLegacyVoxel(u16),
+}
+
+impl fmt::Display for ChunkDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkDecodeError::Decompress(err) => write!(f, "failed to decompress chunk: {}", err),
+            ChunkDecodeError::Deserialize(err) => {
+                write!(f, "failed to deserialize chunk: {}", err)
+            }
+            ChunkDecodeError::UnsupportedVersion(version) => write!(
+                f,
+                "chunk format version {} is newer than supported version {}",
+                version, CHUNK_FORMAT_VERSION
+            ),
+            ChunkDecodeError::MissingMigration(version) => {
+                write!(f, "no migration from chunk format version {}", version)
+            }
+            ChunkDecodeError::UnknownEnvelope => write!(f, "unknown chunk envelope"),
+            ChunkDecodeError::MissingDictionary => {
+                write!(
+                    f,
+                    "chunk needs a compression dictionary the world doesn't have"
+                )
+            }
+            ChunkDecodeError::MissingVoxelIds => {
+                write!(f, "chunk format version 1 needs the voxel ids of the world")
+            }
+            ChunkDecodeError::UnknownLegacyVoxel(texture_id) => {
+                write!(f, "unknown voxel with texture {} in chunk", texture_id)
+            }
+        }
+    }
+}
+
+/// How chunk payloads are compressed, picked per world
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkCodec {
+    None,
+    #[default]
+    Zstd,
+    /// Zstd with a dictionary trained on the chunks of the world,
+    /// small palette chunks share most of their bytes with each other
+    ZstdDict,
+}
+
+impl ChunkCodec {
+    pub const ALL: [ChunkCodec; 3] = [ChunkCodec::None, ChunkCodec::Zstd, ChunkCodec::ZstdDict];
+
+    fn id(self) -> u8 {
+        match self {
+            ChunkCodec::None => 0,
+            ChunkCodec::Zstd => 1,
+            ChunkCodec::ZstdDict => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|codec| codec.id() == id)
+    }
+}
+
+impl fmt::Display for ChunkCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkCodec::None => write!(f, "none"),
+            ChunkCodec::Zstd => write!(f, "zstd"),
+            ChunkCodec::ZstdDict => write!(f, "zstd-dict"),
+        }
+    }
+}
+
+impl FromStr for ChunkCodec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(ChunkCodec::None),
+            "zstd" => Ok(ChunkCodec::Zstd),
+            "zstd-dict" => Ok(ChunkCodec::ZstdDict),
+            other => Err(format!("Unknown chunk codec {}", other)),
+        }
+    }
+}
+
+/// Zstd dictionary prepared once for both directions
+pub struct ChunkDictionary {
+    encoder: EncoderDictionary<'static>,
+    decoder: DecoderDictionary<'static>,
+}
+
+impl ChunkDictionary {
+    pub fn new(dictionary: &[u8], level: i32) -> Self {
+        Self {
+            encoder: EncoderDictionary::copy(dictionary, level),
+            decoder: DecoderDictionary::copy(dictionary),
+        }
+    }
+}
+
+/// Compression settings of a world, used to encode and decode every chunk blob
+#[derive(Clone)]
+pub struct ChunkCompression {
+    codec: ChunkCodec,
+    /// Zstd level, 0 picks the zstd default
+    level: i32,
+    dictionary: Option<Arc<ChunkDictionary>>,
+    /// Needed to migrate chunks saved before voxel ids
+    legacy_voxels: LegacyVoxelIds,
+}
+
+impl ChunkCompression {
+    pub fn new(codec: ChunkCodec, level: i32, dictionary: Option<&[u8]>) -> Self {
+        Self {
+            codec,
+            level,
+            dictionary: dictionary
+                .map(|dictionary| Arc::new(ChunkDictionary::new(dictionary, level))),
+            legacy_voxels: LegacyVoxelIds::default(),
+        }
+    }
+
+    pub fn with_legacy_voxels(mut self, legacy_voxels: LegacyVoxelIds) -> Self {
+        self.legacy_voxels = legacy_voxels;
+        self
+    }
+
+    pub fn from_meta(meta: &WorldMeta) -> Self {
+        Self::new(
+            meta.chunk_codec,
+            meta.compression_level,
+            meta.chunk_dictionary.as_deref(),
+        )
+    }
+
+    pub fn codec(&self) -> ChunkCodec {
+        self.codec
+    }
+
+    /// Serialize a chunk into a blob wrapped in the current envelope
+    pub fn encode(&self, chunk_data: &RawChunk) -> Option<Vec<u8>> {
+        let raw_chunk_bin = bincode::serialize(chunk_data).ok()?;
+        self.encode_bin(&raw_chunk_bin)
+    }
+
+    /// Wrap an already serialized chunk
+    pub fn encode_bin(&self, raw_chunk_bin: &[u8]) -> Option<Vec<u8>> {
+        // Without a dictionary, fall back to plain zstd rather than lose the chunk
+        let codec = match (self.codec, &self.dictionary) {
+            (ChunkCodec::ZstdDict, None) => ChunkCodec::Zstd,
+            (codec, _) => codec,
+        };
+
+        let mut output = Vec::with_capacity(ENVELOPE_HEADER_SIZE);
+        output.extend_from_slice(ENVELOPE_MAGIC);
+        output.extend_from_slice(&CHUNK_FORMAT_VERSION.to_le_bytes());
+        output.push(codec.id());
+
+        match (codec, &self.dictionary) {
+            (ChunkCodec::ZstdDict, Some(dictionary)) => {
+                let mut encoder =
+                    Encoder::with_prepared_dictionary(output, &dictionary.encoder).ok()?;
+                encoder.write_all(raw_chunk_bin).ok()?;
+                encoder.finish().ok()
+            }
+            (ChunkCodec::None, _) => {
+                output.extend_from_slice(raw_chunk_bin);
+                Some(output)
+            }
+            _ => {
+                let mut encoder = Encoder::new(output, self.level).ok()?;
+                encoder.write_all(raw_chunk_bin).ok()?;
+                encoder.finish().ok()
+            }
+        }
+    }
+
+    pub fn decode(&self, blob: &[u8]) -> Result<(ChunkData, Vec<SavedEntity>), ChunkDecodeError> {
+        let raw_chunk_bin = self.decode_bin(blob)?;
+        let raw_chunk: RawChunk =
+            bincode::deserialize(&raw_chunk_bin).map_err(ChunkDecodeError::Deserialize)?;
+
+        Ok(ChunkData::from_raw(raw_chunk))
+    }
+
+    /// Unwrap a blob into serialized chunk bytes of the current version.
+    /// Blobs are read with the codec they were written with, not the current one.
+    pub fn decode_bin(&self, blob: &[u8]) -> Result<Vec<u8>, ChunkDecodeError> {
+        let (version, codec, payload) = open_envelope(blob)?;
+
+        let mut raw_chunk_bin = Vec::new();
+        match codec {
+            ChunkCodec::None => raw_chunk_bin.extend_from_slice(payload),
+            ChunkCodec::Zstd => {
+                Decoder::new(payload)
+                    .and_then(|mut decoder| decoder.read_to_end(&mut raw_chunk_bin))
+                    .map_err(ChunkDecodeError::Decompress)?;
+            }
+            ChunkCodec::ZstdDict => {
+                let dictionary = self
+                    .dictionary
+                    .as_ref()
+                    .ok_or(ChunkDecodeError::MissingDictionary)?;
+                Decoder::with_prepared_dictionary(payload, &dictionary.decoder)
+                    .and_then(|mut decoder| decoder.read_to_end(&mut raw_chunk_bin))
+                    .map_err(ChunkDecodeError::Decompress)?;
+            }
+        }
+
+        migrate(version, raw_chunk_bin, &self.legacy_voxels)
+    }
+}
+
+/// Format version of a stored blob, without decoding it
+pub fn chunk_version(blob: &[u8]) -> Result<u16, ChunkDecodeError> {
+    open_envelope(blob).map(|(version, _codec, _payload)| version)
+}
+
+fn open_envelope(blob: &[u8]) -> Result<(u16, ChunkCodec, &[u8]), ChunkDecodeError> {
+    if blob.starts_with(ZSTD_MAGIC) {
+        return Ok((1, ChunkCodec::Zstd, blob));
+    }
+    if blob.len() < ENVELOPE_MAGIC.len() + 2 || !blob.starts_with(ENVELOPE_MAGIC) {
+        return Err(ChunkDecodeError::UnknownEnvelope);
+    }
+
+    let version = u16::from_le_bytes([blob[4], blob[5]]);
+    if version > CHUNK_FORMAT_VERSION {
+        return Err(ChunkDecodeError::UnsupportedVersion(version));
+    }
+    if version < 3 {
+        return Ok((version, ChunkCodec::Zstd, &blob[6..]));
+    }
+
+    let codec = blob
+        .get(6)
+        .and_then(|id| ChunkCodec::from_id(*id))
+        .ok_or(ChunkDecodeError::UnknownEnvelope)?;
+    Ok((version, codec, &blob[ENVELOPE_HEADER_SIZE..]))
+}
+
+/// Serialized bytes of up to `count` random chunks of a world, unreadable chunks are skipped
+pub fn sample_chunk_bins(
+    chunk_store: &dyn ChunkStore,
+    compression: &ChunkCompression,
+    count: usize,
+) -> io::Result<Vec<Vec<u8>>> {
+    let mut positions = chunk_store.positions()?;
+    positions.shuffle(&mut rand::thread_rng());
+    positions.truncate(count);
+
+    let mut samples = Vec::with_capacity(positions.len());
+    for (_pos, blob) in chunk_store.load_blobs(&positions) {
+        if let Some(blob) = blob? {
+            if let Ok(raw_chunk_bin) = compression.decode_bin(&blob) {
+                samples.push(raw_chunk_bin);
+            }
+        }
+    }
+    Ok(samples)
+}
+
+pub fn train_dictionary(samples: &[Vec<u8>]) -> io::Result<Vec<u8>> {
+    zstd::dict::from_samples(samples, DICTIONARY_SIZE)
+}
+
+/// Decode every stored chunk accepted by `filter` and encode it again, a batch at a time.
+/// Returns how many chunks were rewritten, unreadable ones are quarantined.
+pub fn rewrite_chunks(
+    chunk_store: &dyn ChunkStore,
+    from: &ChunkCompression,
+    to: &ChunkCompression,
+    filter: impl Fn(&[u8]) -> bool,
+) -> io::Result<usize> {
+    const BATCH_SIZE: usize = 256;
+
+    let mut rewritten = 0;
+    for batch in chunk_store.positions()?.chunks(BATCH_SIZE) {
+        let mut blobs: Vec<(ChunkPos, Vec<u8>)> = Vec::new();
+        for (pos, blob) in chunk_store.load_blobs(batch) {
+            let Some(blob) = blob? else {
+                continue;
+            };
+            if !filter(&blob) {
+                continue;
+            }
+
+            match from.decode_bin(&blob) {
+                Ok(raw_chunk_bin) => {
+                    if let Some(blob) = to.encode_bin(&raw_chunk_bin) {
+                        blobs.push((pos, blob));
+                    }
+                }
+                // Leave the whole world alone rather than half rewrite it
+                Err(err @ ChunkDecodeError::UnsupportedVersion(_)) => {
+                    return Err(io::Error::other(err.to_string()));
+                }
+                Err(err) => {
+                    error!("Chunk {:?} is corrupt and was quarantined: {}", pos, err);
+                    chunk_store.quarantine(pos, &err.to_string())?;
+                }
+            }
+        }
+        rewritten += blobs.len();
+        chunk_store.save_blobs(blobs)?;
+    }
+
+    Ok(rewritten)
+}
+
+fn migrate(
+    mut version: u16,
+    mut raw_chunk_bin: Vec<u8>,
+    voxel_ids: &LegacyVoxelIds,
+) -> Result<Vec<u8>, ChunkDecodeError> {
+    while version < CHUNK_FORMAT_VERSION {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|migration| migration.from == version)
+            .ok_or(ChunkDecodeError::MissingMigration(version))?;
+        raw_chunk_bin = (migration.migrate)(raw_chunk_bin, voxel_ids)?;
+        version += 1;
+    }
+    Ok(raw_chunk_bin)
+}
@@ -0,0 +1,248 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use futures_lite::future;
+
+use super::{
+    format::ChunkDecodeError, pending::apply_to_lit_chunk, persist::SavedEntity,
+    saving::SaveCoordinator, ChunkData, ChunkPos, Database, LoadedChunks, PendingWrites,
+    VoxelAddedEvent,
+};
+use crate::{mesher::NeedsMesh, world_generator::NeedsGeneration};
+
+/// Chunks read from disk by a single task
+const LOAD_BATCH_SIZE: usize = 64;
+const MAX_LOADS_PER_FRAME: usize = 4096;
+/// Reads of a chunk before its row is set aside and the chunk regenerated
+const MAX_LOAD_ATTEMPTS: u32 = 5;
+/// Wait before the first retry, doubled after every failed read
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Chunk entity waiting for its data, either from disk or from the generator
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct NeedsChunkData;
+
+/// Chunk entity whose data is being read from disk
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct LoadingChunkData;
+
+/// Chunk whose previous reads failed, read again once `retry_at` is reached
+#[derive(Component)]
+pub struct LoadRetry {
+    failed_attempts: u32,
+    /// Elapsed app time of the next read
+    retry_at: Duration,
+}
+
+enum ChunkLoad {
+    Loaded(ChunkData, Vec<SavedEntity>),
+    /// Never saved, or unreadable and set aside
+    Missing,
+    /// The store couldn't be read, the chunk is left to try again
+    Failed,
+    /// The store kept failing, the row couldn't be set aside either.
+    /// The chunk stays empty so it never overwrites the stored one.
+    GaveUp,
+    /// Saved by a newer build, the chunk stays empty so it is never overwritten
+    Unsupported,
+}
+
+type LoadResult = Vec<(Entity, ChunkPos, ChunkLoad)>;
+
+#[derive(Resource, Default)]
+pub(super) struct ChunkLoadTasks(Vec<Task<LoadResult>>);
+
+/// Read chunks from disk in batches, decoding them on worker threads.
+/// Chunks still waiting to be saved are taken from the save coordinator instead.
+/// Chunks that were never saved, or whose data is unreadable, go to the generator.
+pub(super) fn enqueue_chunk_loading_tasks(
+    mut commands: Commands,
+    database: Res<Database>,
+    save_coordinator: Res<SaveCoordinator>,
+    mut load_tasks: ResMut<ChunkLoadTasks>,
+    needs_data: Query<(Entity, &ChunkPos, Option<&LoadRetry>), With<NeedsChunkData>>,
+) {
+    if needs_data.is_empty() {
+        return;
+    }
+
+    let thread_pool = AsyncComputeTaskPool::get();
+
+    // Chunks whose save hasn't landed yet are taken from memory,
+    // the store still holds an older version of them
+    let mut to_load = Vec::new();
+    let mut unsaved = Vec::new();
+    for (entity, pos, retry) in needs_data.iter().take(MAX_LOADS_PER_FRAME) {
+        let last_attempt = retry.map_or(0, |retry| retry.failed_attempts) + 1 >= MAX_LOAD_ATTEMPTS;
+        match save_coordinator.unsaved_chunk(*pos) {
+            Some(raw) => unsaved.push((entity, *pos, raw)),
+            None => to_load.push((entity, *pos, last_attempt)),
+        }
+    }
+
+    if !unsaved.is_empty() {
+        for (entity, _pos, _raw) in unsaved.iter() {
+            commands
+                .entity(*entity)
+                .remove::<NeedsChunkData>()
+                .insert(LoadingChunkData);
+        }
+
+        load_tasks.0.push(thread_pool.spawn(async move {
+            unsaved
+                .into_iter()
+                .map(|(entity, pos, raw)| {
+                    let (data, entities) = ChunkData::from_raw(raw.as_ref().clone());
+                    (entity, pos, ChunkLoad::Loaded(data, entities))
+                })
+                .collect()
+        }));
+    }
+
+    for batch in to_load.chunks(LOAD_BATCH_SIZE) {
+        for (entity, _pos, _last_attempt) in batch.iter() {
+            commands
+                .entity(*entity)
+                .remove::<NeedsChunkData>()
+                .insert(LoadingChunkData);
+        }
+
+        let batch = batch.to_vec();
+        let chunk_store = database.get_chunk_store();
+        let compression = database.get_compression();
+        load_tasks.0.push(thread_pool.spawn(async move {
+            let positions: Vec<ChunkPos> = batch
+                .iter()
+                .map(|(_entity, pos, _last_attempt)| *pos)
+                .collect();
+            let blobs = chunk_store.load_blobs(&positions);
+
+            batch
+                .into_iter()
+                .zip(blobs)
+                .map(|((entity, pos, last_attempt), (_pos, blob))| {
+                    let load = match blob {
+                        Ok(Some(blob)) => match compression.decode(&blob) {
+                            Ok((data, entities)) => ChunkLoad::Loaded(data, entities),
+                            Err(err @ ChunkDecodeError::UnsupportedVersion(_)) => {
+                                error!("Chunk {:?} can't be loaded: {}", pos, err);
+                                ChunkLoad::Unsupported
+                            }
+                            Err(err) => {
+                                error!(
+                                    "Chunk {:?} is corrupt and will be regenerated: {}",
+                                    pos, err
+                                );
+                                if let Err(err) = chunk_store.quarantine(pos, &err.to_string()) {
+                                    error!("Failed to quarantine chunk {:?}: {}", pos, err);
+                                }
+                                ChunkLoad::Missing
+                            }
+                        },
+                        Ok(None) => ChunkLoad::Missing,
+                        Err(err) if last_attempt => {
+                            error!(
+                                "Failed to read chunk {:?} {} times, setting it aside: {}",
+                                pos, MAX_LOAD_ATTEMPTS, err
+                            );
+                            match chunk_store.quarantine(pos, &err.to_string()) {
+                                Ok(()) => ChunkLoad::Missing,
+                                Err(err) => {
+                                    error!("Failed to quarantine chunk {:?}: {}", pos, err);
+                                    ChunkLoad::GaveUp
+                                }
+                            }
+                        }
+                        Err(err) => {
+                            error!("Failed to read chunk {:?}, will retry: {}", pos, err);
+                            ChunkLoad::Failed
+                        }
+                    };
+                    (entity, pos, load)
+                })
+                .collect()
+        }));
+    }
+}
+
+pub(super) fn handle_done_loading_tasks(
+    mut commands: Commands,
+    world: Res<LoadedChunks>,
+    time: Res<Time>,
+    retries: Query<&LoadRetry>,
+    mut load_tasks: ResMut<ChunkLoadTasks>,
+    mut pending_writes: ResMut<PendingWrites>,
+    mut voxel_added: EventWriter<VoxelAddedEvent>,
+) {
+    let mut loaded = Vec::new();
+    load_tasks.0.retain_mut(|task| {
+        let Some(results) = future::block_on(future::poll_once(task)) else {
+            return true;
+        };
+
+        for (entity, pos, load) in results {
+            // The chunk may have been unloaded while it was read
+            if world.get_chunk(pos) != Some(&entity) {
+                continue;
+            }
+
+            let mut chunk_commands = commands.entity(entity);
+            chunk_commands.remove::<LoadingChunkData>();
+
+            if let ChunkLoad::Failed = load {
+                // Back off so a store that keeps failing isn't hammered every frame
+                let failed_attempts =
+                    retries.get(entity).map_or(0, |retry| retry.failed_attempts) + 1;
+                chunk_commands.insert(LoadRetry {
+                    failed_attempts,
+                    retry_at: time.elapsed() + RETRY_BACKOFF * 2u32.pow(failed_attempts - 1),
+                });
+                continue;
+            }
+            chunk_commands.remove::<LoadRetry>();
+
+            let (mut data, entities) = match load {
+                ChunkLoad::Loaded(data, entities) => (data, entities),
+                ChunkLoad::Missing => {
+                    chunk_commands.insert(NeedsGeneration);
+                    continue;
+                }
+                ChunkLoad::Failed | ChunkLoad::Unsupported | ChunkLoad::GaveUp => continue,
+            };
+
+            if let Some(writes) = pending_writes.take(pos) {
+                voxel_added.send_batch(apply_to_lit_chunk(pos, &mut data, &writes));
+            }
+            chunk_commands.insert(data);
+            for entity in entities {
+                entity.spawn(&mut commands);
+            }
+            loaded.push(pos);
+        }
+
+        false
+    });
+
+    // Re-mesh all neighbors after loading new chunks to simplify geometry
+    for neighbor in world.get_unique_loaded_chunks_and_neighbors(&loaded) {
+        commands.entity(neighbor).insert(NeedsMesh);
+    }
+}
+
+/// Read again the chunks whose backoff is over
+pub(super) fn retry_failed_loads(
+    mut commands: Commands,
+    time: Res<Time>,
+    retries: Query<(Entity, &LoadRetry), (Without<NeedsChunkData>, Without<LoadingChunkData>)>,
+) {
+    for (entity, retry) in retries.iter() {
+        if time.elapsed() >= retry.retry_at {
+            commands.entity(entity).insert(NeedsChunkData);
+        }
+    }
+}
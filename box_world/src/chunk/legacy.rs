@@ -0,0 +1,236 @@
+use std::sync::Arc;
+
+use bevy::utils::HashMap;
+use bitvec::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::format::ChunkDecodeError;
+
+/// Blocks of the first release, indexed by the texture id that identified them in version 1 chunks
+pub const LEGACY_VOXEL_NAMES: [&str; 8] = [
+    "air", "bedrock", "grass", "dirt", "snow", "stone", "glass", "torch",
+];
+
+/// Ids the world gave to the blocks of the first release, indexed like `LEGACY_VOXEL_NAMES`
+#[derive(Clone, Debug, Default)]
+pub struct LegacyVoxelIds(Option<Arc<[u16; LEGACY_VOXEL_NAMES.len()]>>);
+
+impl LegacyVoxelIds {
+    /// Panics if a legacy block has no id, they must be reserved first
+    pub fn new(ids: &HashMap<String, u16>) -> Self {
+        Self(Some(Arc::new(LEGACY_VOXEL_NAMES.map(|name| ids[name]))))
+    }
+}
+
+/// Version 1 palettes held the whole voxel definition, version 2 holds the id of the voxel type.
+/// The storage keeps its layout, only palette entries are rewritten.
+pub fn migrate_voxel_ids(
+    raw_chunk_bin: Vec<u8>,
+    voxel_ids: &LegacyVoxelIds,
+) -> Result<Vec<u8>, ChunkDecodeError> {
+    let ids = voxel_ids
+        .0
+        .as_ref()
+        .ok_or(ChunkDecodeError::MissingVoxelIds)?;
+
+    // The lights following the storage are unchanged and copied as is
+    let mut lights = raw_chunk_bin.as_slice();
+    let storage: Storage<Voxel> =
+        bincode::deserialize_from(&mut lights).map_err(ChunkDecodeError::Deserialize)?;
+    let storage = storage.map_voxels(|voxel| {
+        ids.get(voxel.texture_id as usize)
+            .copied()
+            .ok_or(ChunkDecodeError::UnknownLegacyVoxel(voxel.texture_id))
+    })?;
+
+    let mut migrated = bincode::serialize(&storage).map_err(ChunkDecodeError::Deserialize)?;
+    migrated.extend_from_slice(lights);
+    Ok(migrated)
+}
+
+// Copies of the chunk storage types as serialized by version 1 and 2.
+// Only the palette entries differ, version 2 voxels are a plain u16.
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Visibility {
+    Empty,
+    Transparent,
+    Opaque,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Voxel {
+    visibility: Visibility,
+    texture_id: u16,
+    emissiveness: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Storage<V> {
+    Single(SingleStorage<V>),
+    Multi(MultiStorage<V>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SingleStorage<V> {
+    size: usize,
+    voxel: V,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MultiStorage<V> {
+    size: usize,
+    data: BitBuffer,
+    palette: Vec<PaletteEntry<V>>,
+    palette_capacity: usize,
+    indices_length: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PaletteEntry<V> {
+    voxel_type: V,
+    ref_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BitBuffer {
+    bytes: BitVec<u8, Lsb0>,
+}
+
+impl<V> Storage<V> {
+    fn map_voxels<W>(
+        self,
+        mut map: impl FnMut(V) -> Result<W, ChunkDecodeError>,
+    ) -> Result<Storage<W>, ChunkDecodeError> {
+        Ok(match self {
+            Storage::Single(storage) => Storage::Single(SingleStorage {
+                size: storage.size,
+                voxel: map(storage.voxel)?,
+            }),
+            Storage::Multi(storage) => Storage::Multi(MultiStorage {
+                size: storage.size,
+                data: storage.data,
+                palette: storage
+                    .palette
+                    .into_iter()
+                    .map(|entry| {
+                        Ok(PaletteEntry {
+                            voxel_type: map(entry.voxel_type)?,
+                            ref_count: entry.ref_count,
+                        })
+                    })
+                    .collect::<Result<_, ChunkDecodeError>>()?,
+                palette_capacity: storage.palette_capacity,
+                indices_length: storage.indices_length,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::{
+            format::{ChunkCodec, ChunkCompression},
+            ChunkData,
+        },
+        voxel::Voxel as CurrentVoxel,
+    };
+
+    const STONE_ID: u16 = 6;
+
+    fn legacy_ids() -> LegacyVoxelIds {
+        let ids = LEGACY_VOXEL_NAMES
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| (name.to_string(), [0, 1, 4, 2, 5, 6, 3, 7][idx]))
+            .collect();
+        LegacyVoxelIds::new(&ids)
+    }
+
+    fn legacy_voxel(texture_id: u16) -> Voxel {
+        Voxel {
+            visibility: if texture_id == 0 {
+                Visibility::Empty
+            } else {
+                Visibility::Opaque
+            },
+            texture_id,
+            emissiveness: 0,
+        }
+    }
+
+    /// Bare zstd frame of a chunk of air with stone at its first voxel, as the first release saved it
+    fn baseline_blob() -> Vec<u8> {
+        let size = ChunkData::usize();
+        let indices_length = 2;
+        let mut data = BitVec::<u8, Lsb0>::repeat(false, size * indices_length);
+        data[..indices_length].store_le::<usize>(1);
+
+        let storage = Storage::Multi(MultiStorage {
+            size,
+            data: BitBuffer { bytes: data },
+            palette: vec![
+                PaletteEntry {
+                    voxel_type: legacy_voxel(0),
+                    ref_count: size - 1,
+                },
+                PaletteEntry {
+                    voxel_type: legacy_voxel(5),
+                    ref_count: 1,
+                },
+            ],
+            palette_capacity: 4,
+            indices_length,
+        });
+
+        // Lights are serialized as bytes, bincode prefixes them with their length
+        let mut raw_chunk_bin = bincode::serialize(&storage).unwrap();
+        raw_chunk_bin.extend_from_slice(&(size as u64).to_le_bytes());
+        raw_chunk_bin.extend(std::iter::repeat(0xF0).take(size));
+
+        zstd::encode_all(raw_chunk_bin.as_slice(), 0).unwrap()
+    }
+
+    #[test]
+    fn decodes_baseline_chunk() {
+        let compression =
+            ChunkCompression::new(ChunkCodec::Zstd, 0, None).with_legacy_voxels(legacy_ids());
+        let (data, entities) = compression.decode(&baseline_blob()).unwrap();
+
+        assert_eq!(data.get(0, 0, 0), CurrentVoxel::new(STONE_ID));
+        assert!(data.get(1, 0, 0).is_air());
+        assert!(data.get(15, 15, 15).is_air());
+        assert_eq!(data.get_light(3, 4, 5), 0xF0);
+        assert!(entities.is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_texture() {
+        let storage = Storage::Single(SingleStorage {
+            size: ChunkData::usize(),
+            voxel: legacy_voxel(42),
+        });
+        let raw_chunk_bin = bincode::serialize(&storage).unwrap();
+
+        assert!(matches!(
+            migrate_voxel_ids(raw_chunk_bin, &legacy_ids()),
+            Err(ChunkDecodeError::UnknownLegacyVoxel(42))
+        ));
+    }
+
+    #[test]
+    fn needs_voxel_ids() {
+        let storage = Storage::Single(SingleStorage {
+            size: ChunkData::usize(),
+            voxel: legacy_voxel(0),
+        });
+        let raw_chunk_bin = bincode::serialize(&storage).unwrap();
+
+        assert!(matches!(
+            migrate_voxel_ids(raw_chunk_bin, &LegacyVoxelIds::default()),
+            Err(ChunkDecodeError::MissingVoxelIds)
+        ));
+    }
+}
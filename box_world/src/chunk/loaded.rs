@@ -1,20 +1,41 @@
 use bevy::{
-    prelude::{Component, Entity, Resource},
+    prelude::{info, Component, Entity, IVec3, Resource},
     utils::{HashMap, HashSet},
 };
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
+use super::{
+    benchmark::{self, CodecReport},
+    database,
+    format::{self, ChunkCodec, ChunkCompression, CHUNK_FORMAT_VERSION},
+    legacy::{LegacyVoxelIds, LEGACY_VOXEL_NAMES},
+    meta::{WorldMeta, DEFAULT_GENERATOR, WORLD_FORMAT_VERSION},
+    pending::PendingWrites,
+    persist::PlayerState,
+    store::{self, ChunkStore, ChunkStoreKind, RegionChunkStore, SqliteChunkStore},
+    worlds::WorldSave,
+};
 use crate::chunk::ChunkPos;
 
 #[derive(Resource)]
 pub struct Database {
     pool: Pool<SqliteConnectionManager>,
+    chunk_store: Arc<dyn ChunkStore>,
+    compression: ChunkCompression,
+    regions_path: PathBuf,
+    /// Ids of the blocks chunks from the first release refer to, kept to migrate them
+    legacy_voxels: LegacyVoxelIds,
 }
 
 impl Database {
-    pub fn new() -> Self {
-        let manager = SqliteConnectionManager::file("worlds/world.db3");
+    pub fn open(world: &WorldSave) -> Self {
+        let manager = SqliteConnectionManager::file(world.path());
 
         let pool = Pool::builder()
             .max_size(30)
@@ -31,35 +52,415 @@ impl Database {
                     data blob,
                  PRIMARY KEY (posx, posy, posz)
                 );
+                create table if not exists voxel_ids (
+                    name text not null primary key,
+                    id integer not null unique
+                );
+                create table if not exists quarantined_blocks (
+                    posx integer not null,
+                    posy integer not null,
+                    posz integer not null,
+                    data blob,
+                    reason text,
+                    quarantined_at integer not null default (strftime('%s', 'now'))
+                );
+                create table if not exists pending_writes (
+                    posx integer not null,
+                    posy integer not null,
+                    posz integer not null,
+                    data blob,
+                 PRIMARY KEY (posx, posy, posz)
+                );
+                create table if not exists players (
+                    name text not null primary key,
+                    data blob
+                );
+                create table if not exists world_meta (
+                    key text not null primary key,
+                    value
+                );
                 PRAGMA journal_mode=WAL;
                 PRAGMA synchronous=NORMAL;
             ",
             )
             .unwrap();
 
-        Self { pool }
+        let regions_path = world.regions_path();
+        let meta = database::load_world_meta(&pool);
+        let kind = meta
+            .as_ref()
+            .map(|meta| meta.chunk_store)
+            .unwrap_or_default();
+        let chunk_store =
+            open_chunk_store(kind, &pool, &regions_path).expect("Failed to open chunk store.");
+        let legacy_voxels =
+            LegacyVoxelIds::new(&database::reserve_voxel_ids(&pool, &LEGACY_VOXEL_NAMES));
+        let compression = meta
+            .as_ref()
+            .map(ChunkCompression::from_meta)
+            .unwrap_or_else(|| ChunkCompression::new(ChunkCodec::default(), 0, None))
+            .with_legacy_voxels(legacy_voxels.clone());
+
+        Self {
+            pool,
+            chunk_store,
+            compression,
+            regions_path,
+            legacy_voxels,
+        }
     }
 
     pub fn get_connection_pool(&self) -> Pool<SqliteConnectionManager> {
         self.pool.clone()
     }
+
+    pub fn get_chunk_store(&self) -> Arc<dyn ChunkStore> {
+        self.chunk_store.clone()
+    }
+
+    pub fn get_compression(&self) -> ChunkCompression {
+        self.compression.clone()
+    }
+
+    /// Move every chunk of the world to another storage backend
+    pub fn convert_chunk_store(&mut self, to: ChunkStoreKind) -> io::Result<()> {
+        let mut meta = self.load_or_create_meta();
+        if meta.chunk_store == to {
+            return Ok(());
+        }
+
+        let target = open_chunk_store(to, &self.pool, &self.regions_path)?;
+        let count = store::copy_chunks(self.chunk_store.as_ref(), target.as_ref())?;
+
+        // Only switch once every chunk made it, the old copy is dropped last
+        meta.chunk_store = to;
+        self.save_meta(&meta);
+        self.chunk_store.clear()?;
+        self.chunk_store = target;
+
+        info!("Converted {} chunks to the {} chunk store", count, to);
+        Ok(())
+    }
+
+    /// Rewrite every stored chunk in the latest chunk format.
+    /// Returns how many chunks were upgraded, unreadable ones are quarantined.
+    pub fn upgrade_chunks(&self) -> io::Result<usize> {
+        let upgraded = format::rewrite_chunks(
+            self.chunk_store.as_ref(),
+            &self.compression,
+            &self.compression,
+            |blob| !matches!(format::chunk_version(blob), Ok(CHUNK_FORMAT_VERSION)),
+        )?;
+
+        let mut meta = self.load_or_create_meta();
+        if meta.format_version < WORLD_FORMAT_VERSION {
+            meta.format_version = WORLD_FORMAT_VERSION;
+            self.save_meta(&meta);
+        }
+
+        info!(
+            "Upgraded {} chunks to format version {}",
+            upgraded, CHUNK_FORMAT_VERSION
+        );
+        Ok(upgraded)
+    }
+
+    /// Switch the codec of the world and rewrite every chunk with it.
+    /// The zstd-dict codec trains a new dictionary on the chunks of the world first.
+    pub fn set_compression(&mut self, codec: ChunkCodec, level: i32) -> io::Result<usize> {
+        let mut meta = self.load_or_create_meta();
+
+        // Drop the old dictionary first, so an interrupted switch never
+        // leaves chunks that the saved dictionary can't read
+        if meta.chunk_dictionary.is_some() {
+            let plain = ChunkCompression::new(ChunkCodec::Zstd, level, None)
+                .with_legacy_voxels(self.legacy_voxels.clone());
+            format::rewrite_chunks(
+                self.chunk_store.as_ref(),
+                &self.compression,
+                &plain,
+                |_blob| true,
+            )?;
+            meta.chunk_codec = ChunkCodec::Zstd;
+            meta.chunk_dictionary = None;
+            self.save_meta(&meta);
+            self.compression = plain;
+        }
+
+        let dictionary = if codec == ChunkCodec::ZstdDict {
+            let samples = format::sample_chunk_bins(
+                self.chunk_store.as_ref(),
+                &self.compression,
+                format::DICTIONARY_SAMPLES,
+            )?;
+            Some(format::train_dictionary(&samples)?)
+        } else {
+            None
+        };
+
+        let compression = ChunkCompression::new(codec, level, dictionary.as_deref())
+            .with_legacy_voxels(self.legacy_voxels.clone());
+        meta.chunk_codec = codec;
+        meta.compression_level = level;
+        meta.chunk_dictionary = dictionary;
+        self.save_meta(&meta);
+
+        let count = format::rewrite_chunks(
+            self.chunk_store.as_ref(),
+            &compression,
+            &compression,
+            |_blob| true,
+        )?;
+        self.compression = compression;
+
+        info!(
+            "Compressed {} chunks with {} at level {}",
+            count, codec, level
+        );
+        Ok(count)
+    }
+
+    pub fn benchmark_compression(&self, sample_size: usize) -> io::Result<Vec<CodecReport>> {
+        benchmark::benchmark_codecs(self.chunk_store.as_ref(), &self.compression, sample_size)
+    }
+
+    pub fn reserve_voxel_ids(&self, names: &[&str]) -> HashMap<String, u16> {
+        database::reserve_voxel_ids(&self.pool, names)
+    }
+
+    pub fn load_pending_writes(&self) -> PendingWrites {
+        PendingWrites::new(database::load_pending_writes(&self.pool))
+    }
+
+    pub fn load_player(&self) -> Option<PlayerState> {
+        database::load_player(&self.pool)
+    }
+
+    pub fn load_meta(&self) -> Option<WorldMeta> {
+        database::load_world_meta(&self.pool)
+    }
+
+    pub fn save_meta(&self, meta: &WorldMeta) {
+        database::save_world_meta(&self.pool, meta);
+    }
+
+    /// Read the world metadata, creating it with a random seed for a new world.
+    /// Worlds that already have chunks but no metadata keep the seed they were generated with.
+    pub fn load_or_create_meta(&self) -> WorldMeta {
+        if let Some(meta) = self.load_meta() {
+            return meta;
+        }
+
+        let meta = if database::has_chunks(&self.pool) {
+            WorldMeta::legacy()
+        } else {
+            WorldMeta::new(rand::random(), DEFAULT_GENERATOR)
+        };
+        self.save_meta(&meta);
+        meta
+    }
+}
+
+fn open_chunk_store(
+    kind: ChunkStoreKind,
+    pool: &Pool<SqliteConnectionManager>,
+    regions_path: &Path,
+) -> io::Result<Arc<dyn ChunkStore>> {
+    Ok(match kind {
+        ChunkStoreKind::Sqlite => Arc::new(SqliteChunkStore::new(pool.clone())),
+        ChunkStoreKind::Region => Arc::new(RegionChunkStore::new(regions_path)?),
+    })
+}
+
+/// Shape of the chunks kept loaded around a load point
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoadShape {
+    /// `horizontal` chunks around, `vertical` chunks up and down
+    #[default]
+    Cylinder,
+    Ellipsoid,
+    Box,
+}
+
+impl LoadShape {
+    /// Whether a chunk at this offset from the center is inside the shape
+    fn contains(self, offset: IVec3, horizontal: u32, vertical: u32) -> bool {
+        let (x, y, z) = (offset.x as i64, offset.y as i64, offset.z as i64);
+        let (horizontal, vertical) = (horizontal as i64, vertical as i64);
+        match self {
+            LoadShape::Cylinder => y.abs() <= vertical && x * x + z * z <= horizontal * horizontal,
+            // A zero radius flattens the ellipsoid to a single layer or a single column
+            LoadShape::Ellipsoid if vertical == 0 => {
+                y == 0 && x * x + z * z <= horizontal * horizontal
+            }
+            LoadShape::Ellipsoid if horizontal == 0 => x == 0 && z == 0 && y.abs() <= vertical,
+            LoadShape::Ellipsoid => {
+                (x * x + z * z) * vertical * vertical + y * y * horizontal * horizontal
+                    <= horizontal * horizontal * vertical * vertical
+            }
+            LoadShape::Box => x.abs() <= horizontal && y.abs() <= vertical && z.abs() <= horizontal,
+        }
+    }
+
+    /// Furthest x offset inside the shape on the row at these y and z offsets, if the row is in it.
+    /// Every shape is convex and symmetric, so the row spans every x up to that offset.
+    fn half_width(self, y: i32, z: i32, horizontal: u32, vertical: u32) -> Option<i32> {
+        if !self.contains(IVec3::new(0, y, z), horizontal, vertical) {
+            return None;
+        }
+        let (mut inside, mut outside) = (0, horizontal as i32 + 1);
+        while outside - inside > 1 {
+            let x = (inside + outside) / 2;
+            if self.contains(IVec3::new(x, y, z), horizontal, vertical) {
+                inside = x;
+            } else {
+                outside = x;
+            }
+        }
+        Some(inside)
+    }
+}
+
+/// What the chunks around a load point are loaded for, each level includes the previous ones.
+/// Chunks covered by several load points get the highest level among them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LoadPolicy {
+    /// Only the chunk data, for server-side or spectator load points
+    LoadOnly,
+    Mesh,
+    #[default]
+    MeshAndPhysics,
+}
+
+impl LoadPolicy {
+    pub fn meshes(self) -> bool {
+        self >= LoadPolicy::Mesh
+    }
+
+    pub fn has_physics(self) -> bool {
+        self >= LoadPolicy::MeshAndPhysics
+    }
 }
 
 #[derive(Component, Default)]
 pub struct LoadPoint {
     pub horizontal: u32,
     pub vertical: u32,
+    pub shape: LoadShape,
+    pub policy: LoadPolicy,
+}
+
+/// Chunks kept loaded around a load point standing in `center`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct LoadRange {
+    pub center: ChunkPos,
+    pub horizontal: u32,
+    pub vertical: u32,
+    pub shape: LoadShape,
+    pub policy: LoadPolicy,
+}
+
+impl LoadRange {
+    pub fn new(center: ChunkPos, load_point: &LoadPoint) -> Self {
+        Self {
+            center,
+            horizontal: load_point.horizontal,
+            vertical: load_point.vertical,
+            shape: load_point.shape,
+            policy: load_point.policy,
+        }
+    }
+
+    pub fn contains(&self, pos: ChunkPos) -> bool {
+        self.shape
+            .contains(*pos - *self.center, self.horizontal, self.vertical)
+    }
+
+    fn chunks(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        let horizontal = self.horizontal as i32;
+        let vertical = self.vertical as i32;
+        (-horizontal..=horizontal)
+            .flat_map(move |z| {
+                (-vertical..=vertical).flat_map(move |y| {
+                    (-horizontal..=horizontal).map(move |x| {
+                        ChunkPos::new(self.center.x + x, self.center.y + y, self.center.z + z)
+                    })
+                })
+            })
+            .filter(|pos| self.contains(*pos))
+    }
+
+    /// Bounds of the x coordinates this range covers on the row at these y and z coordinates
+    fn row(&self, y: i32, z: i32) -> Option<(i32, i32)> {
+        let half_width = self.shape.half_width(
+            y - self.center.y,
+            z - self.center.z,
+            self.horizontal,
+            self.vertical,
+        )?;
+        Some((self.center.x - half_width, self.center.x + half_width))
+    }
+
+    /// Chunks of this range that `other` doesn't load, or loads under another policy
+    fn chunks_not_in<'a>(
+        &'a self,
+        other: Option<&'a LoadRange>,
+    ) -> Box<dyn Iterator<Item = ChunkPos> + 'a> {
+        let Some(other) = other.filter(|other| other.policy == self.policy) else {
+            return Box::new(self.chunks());
+        };
+        if (other.shape, other.horizontal, other.vertical)
+            != (self.shape, self.horizontal, self.vertical)
+        {
+            return Box::new(self.chunks().filter(|pos| !other.contains(*pos)));
+        }
+
+        // Only the center moved, each row only gains or loses chunks at its ends
+        let horizontal = self.horizontal as i32;
+        let vertical = self.vertical as i32;
+        Box::new(
+            (-horizontal..=horizontal)
+                .flat_map(move |z| (-vertical..=vertical).map(move |y| (y, z)))
+                .filter_map(move |(y, z)| {
+                    let (y, z) = (self.center.y + y, self.center.z + z);
+                    let (start, end) = self.row(y, z)?;
+                    Some((y, z, start, end, other.row(y, z)))
+                })
+                .flat_map(|(y, z, start, end, other_row)| {
+                    let (before, after) = match other_row {
+                        Some((other_start, other_end)) => (
+                            start..=end.min(other_start - 1),
+                            start.max(other_end + 1)..=end,
+                        ),
+                        // Empty second half, the whole row is outside the other range
+                        None => (start..=end, 1..=0),
+                    };
+                    before.chain(after).map(move |x| ChunkPos::new(x, y, z))
+                }),
+        )
+    }
+}
+
+/// Chunks that entered or left the range of every load point since the last update
+pub struct RangeChanges {
+    pub entered: Vec<ChunkPos>,
+    pub exited: Vec<Entity>,
+    /// Chunks that stay loaded under another policy, their mesh and colliders need a rebuild
+    pub policy_changed: Vec<Entity>,
 }
 
 #[derive(Resource)]
 pub struct LoadedChunks {
     chunks: HashMap<ChunkPos, Entity>,
+    /// Range of each load point as of the last update
+    ranges: HashMap<Entity, LoadRange>,
 }
 
 impl LoadedChunks {
     pub fn new() -> Self {
         Self {
             chunks: HashMap::new(),
+            ranges: HashMap::new(),
         }
     }
 
@@ -67,49 +468,83 @@ impl LoadedChunks {
         self.chunks.insert(pos, entity);
     }
 
-    pub fn load_inside_range(&mut self, pos_lit: &[(ChunkPos, u32, u32)]) -> Vec<ChunkPos> {
-        let tentative_max_chunks = (pos_lit[0].1 * pos_lit[0].1) as usize;
-        let mut to_load = Vec::with_capacity(tentative_max_chunks);
+    /// Diff the new range of each load point against the previous one.
+    /// Only the load points that crossed a chunk boundary, appeared, disappeared
+    /// or changed settings are walked, and only the chunks on the edge that moved are returned.
+    /// A load point that only moved walks the ends of its rows, its whole range is only walked
+    /// when it appears or its settings change.
+    /// Exited chunks are forgotten here, the caller despawns them.
+    /// Returns nothing when no load point changed.
+    pub fn update_ranges(&mut self, ranges: HashMap<Entity, LoadRange>) -> Option<RangeChanges> {
+        if ranges == self.ranges {
+            return None;
+        }
 
-        for (pos, horizontal_distance, vertical_distance) in pos_lit.iter().copied() {
-            let horizontal_distance = horizontal_distance as i32;
-            let horizontal_distance_squared = horizontal_distance * horizontal_distance;
-            let vertical_distance = vertical_distance as i32;
+        // Loaded chunks that stay loaded, but whose covering load points changed
+        let mut touched = HashSet::new();
 
-            for z in -horizontal_distance..=horizontal_distance {
-                for y in -vertical_distance..=vertical_distance {
-                    for x in -horizontal_distance..=horizontal_distance {
-                        let other_pos = ChunkPos::new(pos.x + x, pos.y + y, pos.z + z);
+        let mut entered = HashSet::new();
+        for (load_point, range) in ranges.iter() {
+            let previous = self.ranges.get(load_point);
+            if previous == Some(range) {
+                continue;
+            }
+            for pos in range.chunks_not_in(previous) {
+                if self.chunks.contains_key(&pos) {
+                    touched.insert(pos);
+                } else {
+                    entered.insert(pos);
+                }
+            }
+        }
 
-                        let chunk_distance = pos.distance_squared(&other_pos);
-                        if chunk_distance <= horizontal_distance_squared as f32 {
-                            to_load.push(other_pos);
-                        }
-                    }
+        let mut exited = HashSet::new();
+        for (load_point, previous) in self.ranges.iter() {
+            let range = ranges.get(load_point);
+            if range == Some(previous) {
+                continue;
+            }
+            for pos in previous.chunks_not_in(range) {
+                if !self.chunks.contains_key(&pos) {
+                    continue;
+                }
+                if ranges.values().any(|range| range.contains(pos)) {
+                    touched.insert(pos);
+                } else {
+                    exited.insert(pos);
                 }
             }
         }
 
-        to_load
-    }
+        let policy_changed = touched
+            .into_iter()
+            .filter(|pos| policy_in(&self.ranges, *pos) != policy_in(&ranges, *pos))
+            .filter_map(|pos| self.chunks.get(&pos).copied())
+            .collect();
 
-    fn unload(&mut self, pos: ChunkPos) -> Entity {
-        self.chunks
-            .remove(&pos)
-            .expect("Chunk should exist at ChunkPos for unloading")
+        self.ranges = ranges;
+        Some(RangeChanges {
+            entered: entered.into_iter().collect(),
+            exited: exited
+                .into_iter()
+                .filter_map(|pos| self.chunks.remove(&pos))
+                .collect(),
+            policy_changed,
+        })
     }
 
-    pub fn unload_outside_range(&mut self, pos_list: &[(ChunkPos, u32, u32)]) -> Vec<Entity> {
-        let mut to_remove = Vec::new();
-        self.chunks.keys().for_each(|other_pos| {
-            if pos_list.iter().all(|(pos, horizontal, _vertical)| {
-                pos.distance_squared(other_pos) > (horizontal * horizontal) as f32
-            }) {
-                to_remove.push(*other_pos);
-            }
-        });
+    /// Highest policy among the load points covering a chunk
+    pub fn policy_at(&self, pos: ChunkPos) -> Option<LoadPolicy> {
+        policy_in(&self.ranges, pos)
+    }
 
-        to_remove.into_iter().map(|pos| self.unload(pos)).collect()
+    /// Distance in chunks to the nearest load point that wants meshes
+    pub fn mesh_distance(&self, pos: ChunkPos) -> Option<f32> {
+        self.ranges
+            .values()
+            .filter(|range| range.policy.meshes())
+            .map(|range| range.center.distance(&pos))
+            .min_by(f32::total_cmp)
     }
 
     pub fn get_chunk(&self, pos: ChunkPos) -> Option<&Entity> {
@@ -139,3 +574,119 @@ impl LoadedChunks {
         set.into_iter().collect()
     }
 }
+
+fn policy_in(ranges: &HashMap<Entity, LoadRange>, pos: ChunkPos) -> Option<LoadPolicy> {
+    ranges
+        .values()
+        .filter(|range| range.contains(pos))
+        .map(|range| range.policy)
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(shape: LoadShape, horizontal: u32, vertical: u32) -> Vec<IVec3> {
+        let (h, v) = (horizontal as i32 + 1, vertical as i32 + 1);
+        let mut offsets = Vec::new();
+        for x in -h..=h {
+            for y in -v..=v {
+                for z in -h..=h {
+                    let offset = IVec3::new(x, y, z);
+                    if shape.contains(offset, horizontal, vertical) {
+                        offsets.push(offset);
+                    }
+                }
+            }
+        }
+        offsets
+    }
+
+    #[test]
+    fn cylinder_contains() {
+        let shape = LoadShape::Cylinder;
+        assert!(shape.contains(IVec3::new(3, 2, 0), 3, 2));
+        assert!(shape.contains(IVec3::new(0, -2, -3), 3, 2));
+        assert!(!shape.contains(IVec3::new(0, 3, 0), 3, 2));
+        assert!(!shape.contains(IVec3::new(3, 0, 1), 3, 2));
+        assert!(!shape.contains(IVec3::new(2, 0, 3), 3, 2));
+        assert_eq!(offsets(shape, 3, 2).len(), 29 * 5);
+    }
+
+    #[test]
+    fn ellipsoid_contains() {
+        let shape = LoadShape::Ellipsoid;
+        assert!(shape.contains(IVec3::new(4, 0, 0), 4, 2));
+        assert!(shape.contains(IVec3::new(0, -2, 0), 4, 2));
+        assert!(shape.contains(IVec3::new(2, 1, 0), 4, 2));
+        assert!(!shape.contains(IVec3::new(4, 1, 0), 4, 2));
+        assert!(!shape.contains(IVec3::new(0, 3, 0), 4, 2));
+        assert!(!shape.contains(IVec3::new(3, 1, 3), 4, 2));
+    }
+
+    #[test]
+    fn box_contains() {
+        let shape = LoadShape::Box;
+        assert!(shape.contains(IVec3::new(3, 2, -3), 3, 2));
+        assert!(!shape.contains(IVec3::new(4, 0, 0), 3, 2));
+        assert!(!shape.contains(IVec3::new(0, -3, 0), 3, 2));
+        assert_eq!(offsets(shape, 3, 2).len(), 7 * 5 * 7);
+    }
+
+    #[test]
+    fn zero_vertical_radius_is_a_layer() {
+        for shape in [LoadShape::Cylinder, LoadShape::Ellipsoid, LoadShape::Box] {
+            let offsets = offsets(shape, 2, 0);
+            assert!(offsets.iter().all(|offset| offset.y == 0), "{:?}", shape);
+            assert!(offsets.contains(&IVec3::new(2, 0, 0)), "{:?}", shape);
+            assert!(offsets.contains(&IVec3::new(0, 0, -2)), "{:?}", shape);
+        }
+        assert_eq!(offsets(LoadShape::Ellipsoid, 2, 0).len(), 13);
+    }
+
+    #[test]
+    fn zero_horizontal_radius_is_a_column() {
+        for shape in [LoadShape::Cylinder, LoadShape::Ellipsoid, LoadShape::Box] {
+            let offsets = offsets(shape, 0, 2);
+            let column: Vec<IVec3> = (-2..=2).map(|y| IVec3::new(0, y, 0)).collect();
+            assert_eq!(offsets, column, "{:?}", shape);
+        }
+    }
+
+    #[test]
+    fn moved_range_only_walks_the_difference() {
+        for shape in [LoadShape::Cylinder, LoadShape::Ellipsoid, LoadShape::Box] {
+            let load_point = LoadPoint {
+                horizontal: 4,
+                vertical: 2,
+                shape,
+                ..Default::default()
+            };
+            let previous = LoadRange::new(ChunkPos::new(0, 0, 0), &load_point);
+            for center in [
+                ChunkPos::new(1, 0, 0),
+                ChunkPos::new(-1, 1, 0),
+                ChunkPos::new(2, -1, -3),
+                ChunkPos::new(20, 0, 0),
+            ] {
+                let range = LoadRange::new(center, &load_point);
+                let mut walked: Vec<ChunkPos> = range.chunks_not_in(Some(&previous)).collect();
+                let mut expected: Vec<ChunkPos> = range
+                    .chunks()
+                    .filter(|pos| !previous.contains(*pos))
+                    .collect();
+                walked.sort_by_key(|pos| (pos.x, pos.y, pos.z));
+                expected.sort_by_key(|pos| (pos.x, pos.y, pos.z));
+                assert_eq!(walked, expected, "{:?} to {:?}", shape, center);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_radii_are_the_center() {
+        for shape in [LoadShape::Cylinder, LoadShape::Ellipsoid, LoadShape::Box] {
+            assert_eq!(offsets(shape, 0, 0), vec![IVec3::ZERO], "{:?}", shape);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::{prelude::Resource, utils::HashMap};
+use noise::{OpenSimplex, RidgedMulti};
+use rusqlite::types::Value;
+
+use super::{format::ChunkCodec, store::ChunkStoreKind};
+use crate::voxel::GlobalVoxelPos;
+
+/// Version of the on-disk world format written by this build.
+/// Version 2 wraps every chunk in a versioned envelope.
+pub const WORLD_FORMAT_VERSION: u32 = 2;
+
+pub const DEFAULT_GENERATOR: &str = "ridged";
+
+/// Seed of the terrain noise before worlds had their own, the noise crate default
+pub const LEGACY_SEED: u32 = RidgedMulti::<OpenSimplex>::DEFAULT_SEED;
+
+/// World wide settings, stored as key/value rows in the `world_meta` table
+#[derive(Resource, Clone, Debug)]
+pub struct WorldMeta {
+    pub seed: u32,
+    pub generator: String,
+    /// Generator specific settings, such as the layers of a superflat world
+    pub generator_options: String,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    pub format_version: u32,
+    pub spawn_point: GlobalVoxelPos,
+    /// Where chunks are saved, worlds from before the setting existed use sqlite
+    pub chunk_store: ChunkStoreKind,
+    pub chunk_codec: ChunkCodec,
+    /// Zstd level for new chunk blobs, 0 picks the zstd default
+    pub compression_level: i32,
+    /// Trained zstd dictionary used by the zstd-dict codec
+    pub chunk_dictionary: Option<Vec<u8>>,
+}
+
+impl WorldMeta {
+    pub fn new(seed: u32, generator: &str) -> Self {
+        Self {
+            seed,
+            generator: generator.to_string(),
+            generator_options: String::new(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0),
+            format_version: WORLD_FORMAT_VERSION,
+            spawn_point: GlobalVoxelPos::new(5000, 200, 5000),
+            chunk_store: ChunkStoreKind::default(),
+            chunk_codec: ChunkCodec::default(),
+            compression_level: 0,
+            chunk_dictionary: None,
+        }
+    }
+
+    /// Metadata of a world saved before worlds had any, so new chunks match the saved terrain
+    pub fn legacy() -> Self {
+        Self {
+            format_version: 1,
+            ..Self::new(LEGACY_SEED, DEFAULT_GENERATOR)
+        }
+    }
+
+    pub fn from_values(values: &HashMap<String, Value>) -> Option<Self> {
+        let integer = |key: &str| match values.get(key) {
+            Some(Value::Integer(value)) => Some(*value),
+            _ => None,
+        };
+        let text = |key: &str| match values.get(key) {
+            Some(Value::Text(value)) => Some(value.clone()),
+            _ => None,
+        };
+        let blob = |key: &str| match values.get(key) {
+            Some(Value::Blob(value)) => Some(value.clone()),
+            _ => None,
+        };
+
+        Some(Self {
+            seed: integer("seed")? as u32,
+            generator: text("generator")?,
+            generator_options: text("generator_options").unwrap_or_default(),
+            created_at: integer("created_at")? as u64,
+            format_version: integer("format_version")? as u32,
+            spawn_point: GlobalVoxelPos::new(
+                integer("spawn_x")? as i32,
+                integer("spawn_y")? as i32,
+                integer("spawn_z")? as i32,
+            ),
+            chunk_store: text("chunk_store")
+                .and_then(|kind| kind.parse().ok())
+                .unwrap_or_default(),
+            chunk_codec: text("chunk_codec")
+                .and_then(|codec| codec.parse().ok())
+                .unwrap_or_default(),
+            compression_level: integer("compression_level").unwrap_or(0) as i32,
+            chunk_dictionary: blob("chunk_dictionary"),
+        })
+    }
+
+    pub fn to_values(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("seed", Value::Integer(self.seed as i64)),
+            ("generator", Value::Text(self.generator.clone())),
+            (
+                "generator_options",
+                Value::Text(self.generator_options.clone()),
+            ),
+            ("created_at", Value::Integer(self.created_at as i64)),
+            ("format_version", Value::Integer(self.format_version as i64)),
+            ("spawn_x", Value::Integer(self.spawn_point.x as i64)),
+            ("spawn_y", Value::Integer(self.spawn_point.y as i64)),
+            ("spawn_z", Value::Integer(self.spawn_point.z as i64)),
+            ("chunk_store", Value::Text(self.chunk_store.to_string())),
+            ("chunk_codec", Value::Text(self.chunk_codec.to_string())),
+            (
+                "compression_level",
+                Value::Integer(self.compression_level as i64),
+            ),
+            (
+                "chunk_dictionary",
+                self.chunk_dictionary
+                    .clone()
+                    .map_or(Value::Null, Value::Blob),
+            ),
+        ]
+    }
+}
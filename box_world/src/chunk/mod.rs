@@ -1,23 +1,43 @@
-use crate::{
-    voxel::{GlobalVoxelPos, Voxel},
-    world_generator::NeedsChunkData,
-};
+use crate::mesher::NeedsMesh;
+use crate::voxel::{GlobalVoxelPos, Voxel};
 use bevy::app::AppExit;
 use bevy::prelude::*;
-use bevy::tasks::AsyncComputeTaskPool;
 use rand::seq::IteratorRandom;
 
+mod benchmark;
 mod data;
 mod database;
+mod format;
+mod io;
+mod legacy;
 mod lighting;
 mod loaded;
+mod meta;
+mod pending;
+mod persist;
 mod position;
+mod priority;
+mod saving;
 mod storage;
+mod store;
+mod worlds;
 
+pub use benchmark::CodecReport;
 pub use data::ChunkData;
+pub use format::ChunkCodec;
+pub use io::NeedsChunkData;
 pub use lighting::{to_sunlight, to_torchlight};
-pub use loaded::{Database, LoadPoint, LoadedChunks};
+pub use loaded::{Database, LoadPoint, LoadPolicy, LoadRange, LoadShape, LoadedChunks};
+pub use meta::{WorldMeta, WORLD_FORMAT_VERSION};
+pub use pending::{apply_to_lit_chunk, PendingWrite, PendingWrites};
+pub use persist::{BlockEntity, Persist, PlayerState};
 pub use position::ChunkPos;
+pub use priority::ChunkPriorities;
+pub use store::ChunkStoreKind;
+pub use worlds::{WorldSave, Worlds};
+
+use persist::{saved_entities_by_chunk, PersistedEntities, SavedEntity};
+use saving::SaveCoordinator;
 
 #[derive(Event)]
 pub struct VoxelAddedEvent {
@@ -42,21 +62,81 @@ impl VoxelRemovedEvent {
     }
 }
 
-pub struct ChunkPlugin;
+/// Sent when a load point crossed into another chunk, appeared, disappeared or changed settings
+#[derive(Event)]
+pub struct LoadRangesChangedEvent;
+
+/// Loads, generates and saves the chunks of a single world
+pub struct ChunkPlugin {
+    pub world: WorldSave,
+}
+
+impl ChunkPlugin {
+    pub fn new(world: WorldSave) -> Self {
+        Self { world }
+    }
+}
 
 impl Plugin for ChunkPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.insert_resource(LoadedChunks::new())
-            .insert_resource(Database::new())
-            .insert_resource(AutosaveTimer::new());
+        let database = Database::open(&self.world);
+        let world_meta = database.load_or_create_meta();
+        info!(
+            "Opened world {} with seed {} and generator {}, created at {}",
+            self.world.name(),
+            world_meta.seed,
+            world_meta.generator,
+            world_meta.created_at
+        );
+        if world_meta.format_version > WORLD_FORMAT_VERSION {
+            warn!(
+                "World format version {} is newer than supported version {}",
+                world_meta.format_version, WORLD_FORMAT_VERSION
+            );
+        }
 
-        app.add_systems(Update, (periodic_chunk_trim, autosave_chunks))
-            .add_systems(PreUpdate, load_around_load_points);
+        let pending_writes = database.load_pending_writes();
+
+        app.insert_resource(LoadedChunks::new())
+            .insert_resource(pending_writes)
+            .insert_resource(SaveCoordinator::new(&database))
+            .insert_resource(database)
+            .insert_resource(world_meta);
+
+        app.init_resource::<io::ChunkLoadTasks>()
+            .init_resource::<PersistedEntities>()
+            .init_resource::<ChunkPriorities>();
+
+        app.add_systems(
+            Update,
+            (
+                periodic_chunk_trim,
+                persist::track_persisted_entities,
+                saving::save_dirty_chunks.after(persist::track_persisted_entities),
+                io::retry_failed_loads,
+                io::enqueue_chunk_loading_tasks.after(io::retry_failed_loads),
+            ),
+        )
+        .add_systems(
+            PreUpdate,
+            (load_around_load_points, priority::update_chunk_priorities),
+        )
+        .add_systems(
+            PostUpdate,
+            (
+                io::handle_done_loading_tasks,
+                saving::handle_done_save_tasks,
+                saving::journal_changes
+                    .after(io::handle_done_loading_tasks)
+                    .after(saving::handle_done_save_tasks),
+            ),
+        );
 
-        app.add_systems(Last, save_chunks_on_close.run_if(on_event::<AppExit>()));
+        app.add_systems(Last, saving::flush_on_exit.run_if(on_event::<AppExit>()));
 
         app.add_event::<VoxelAddedEvent>()
-            .add_event::<VoxelRemovedEvent>();
+            .add_event::<VoxelRemovedEvent>()
+            .add_event::<LoadRangesChangedEvent>();
     }
 }
 
@@ -74,126 +154,64 @@ fn periodic_chunk_trim(mut chunks: Query<&mut ChunkData>) {
 fn load_around_load_points(
     mut commands: Commands,
     mut world: ResMut<LoadedChunks>,
-    database: Res<Database>,
-    load_query: Query<(Ref<Transform>, &LoadPoint)>,
+    mut save_coordinator: ResMut<SaveCoordinator>,
+    load_query: Query<(Entity, &Transform, &LoadPoint)>,
     chunks: Query<(&ChunkPos, &ChunkData)>,
+    persisted: Query<(Entity, &Persist, &Transform)>,
+    mut ranges_changed: EventWriter<LoadRangesChangedEvent>,
 ) {
-    let mut load_pos = Vec::new();
-    for (load_transform, load_distance) in load_query.iter() {
-        let load_translation = load_transform.translation;
-        let load_chunk_pos = ChunkPos::from_global_coords(load_translation);
-        load_pos.push((
-            load_chunk_pos,
-            load_distance.horizontal,
-            load_distance.vertical,
-        ));
-    }
-
-    if load_query
+    let ranges = load_query
         .iter()
-        .any(|(transform, _distance)| transform.is_changed())
-    {
-        {
-            let _span = info_span!("Unloading chunks").entered();
-            let unloaded_chunks = world.unload_outside_range(&load_pos);
-            let mut to_save = Vec::new();
-            for entity in unloaded_chunks.iter() {
-                if let Ok((chunk_pos, chunk_data)) = chunks.get(*entity) {
-                    if chunk_data.is_dirty() {
-                        to_save.push((*chunk_pos, chunk_data.to_raw()));
-                    }
+        .map(|(entity, transform, load_point)| {
+            let center = ChunkPos::from_global_coords(transform.translation);
+            (entity, LoadRange::new(center, load_point))
+        })
+        .collect();
+
+    let Some(changes) = world.update_ranges(ranges) else {
+        return;
+    };
+    ranges_changed.send(LoadRangesChangedEvent);
+
+    if !changes.exited.is_empty() {
+        let _span = info_span!("Unloading chunks").entered();
+        let mut saved_entities = saved_entities_by_chunk(&persisted);
+        let mut to_save = Vec::new();
+        for entity in changes.exited.iter() {
+            if let Ok((chunk_pos, chunk_data)) = chunks.get(*entity) {
+                // Persisted entities leave with their chunk
+                let (entities, saved): (Vec<Entity>, Vec<SavedEntity>) = saved_entities
+                    .remove(chunk_pos)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .unzip();
+                for entity in entities {
+                    commands.entity(entity).despawn_recursive();
+                }
+                if chunk_data.is_dirty() {
+                    to_save.push((*chunk_pos, chunk_data.to_raw(saved)));
                 }
-                commands.entity(*entity).despawn_recursive();
-            }
-
-            if !to_save.is_empty() {
-                let thread_pool = AsyncComputeTaskPool::get();
-                let connection_lock = database.get_connection_pool();
-                thread_pool
-                    .spawn(async move {
-                        info!("Saving {} unloaded chunks", to_save.len());
-                        database::save_raw_chunks(&connection_lock, to_save);
-                    })
-                    .detach();
             }
+            commands.entity(*entity).despawn_recursive();
         }
 
-        {
-            let _span = info_span!("Loading chunks").entered();
-            let loaded_chunks = world.load_inside_range(&load_pos);
-            for pos in loaded_chunks.into_iter() {
-                if world.get_chunk(pos).is_none() {
-                    let entity = commands
-                        .spawn((pos, NeedsChunkData, Name::new("Chunk")))
-                        .id();
-                    world.set(pos, entity);
-                }
-            }
+        if !to_save.is_empty() {
+            info!("Saving {} unloaded chunks", to_save.len());
+            save_coordinator.save_unloaded(to_save);
         }
     }
-}
 
-#[derive(Resource)]
-struct AutosaveTimer(pub Timer);
-
-impl AutosaveTimer {
-    fn new() -> Self {
-        Self(Timer::from_seconds(60.0, TimerMode::Repeating))
+    for entity in changes.policy_changed {
+        commands.entity(entity).insert(NeedsMesh);
     }
-}
 
-fn autosave_chunks(
-    database: Res<Database>,
-    time: Res<Time>,
-    mut autosave_timer: ResMut<AutosaveTimer>,
-    mut chunks: Query<(&ChunkPos, &mut ChunkData)>,
-) {
-    if autosave_timer.0.tick(time.delta()).just_finished() {
-        info!("Starting autosave");
-
-        // Autosave needs to be fast, so we only save a small batch of chunks at a time
-        let mut chunks_cloned = Vec::new();
-        for (chunk_pos, mut chunk_data) in chunks
-            .iter_mut()
-            .filter(|(_pos, data)| data.is_dirty())
-            .take(5000)
-        {
-            chunk_data.set_dirty(false);
-            chunks_cloned.push((*chunk_pos, chunk_data.to_raw()));
+    if !changes.entered.is_empty() {
+        let _span = info_span!("Loading chunks").entered();
+        for pos in changes.entered.into_iter() {
+            let entity = commands
+                .spawn((pos, NeedsChunkData, Name::new("Chunk")))
+                .id();
+            world.set(pos, entity);
         }
-
-        let thread_pool = AsyncComputeTaskPool::get();
-        let connection_lock = database.get_connection_pool();
-        thread_pool
-            .spawn(async move {
-                database::save_raw_chunks(&connection_lock, chunks_cloned);
-                info!("Done autosaving");
-            })
-            .detach();
-    }
-}
-
-fn save_chunks_on_close(
-    exit: EventReader<AppExit>,
-    database: Res<Database>,
-    chunks: Query<(&ChunkPos, &ChunkData)>,
-) {
-    if !exit.is_empty() {
-        info!("Save on close");
-
-        let connection_lock = database.get_connection_pool();
-        database::save_raw_chunks(
-            &connection_lock,
-            chunks
-                .iter()
-                .filter_map(|(pos, data)| {
-                    if data.is_dirty() {
-                        Some((*pos, data.to_raw()))
-                    } else {
-                        None
-                    }
-                })
-                .collect(),
-        );
     }
 }
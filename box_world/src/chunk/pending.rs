@@ -0,0 +1,106 @@
+use bevy::{prelude::Resource, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use super::{ChunkData, ChunkPos, VoxelAddedEvent};
+use crate::voxel::{ChunkLocalVoxelPos, GlobalVoxelPos, Voxel};
+
+/// Voxel write aimed at a chunk that wasn't generated or loaded when it was produced
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PendingWrite {
+    idx: u16,
+    voxel: Voxel,
+    /// The write is skipped unless the voxel still holds this value,
+    /// so decorations never overwrite terrain or player changes
+    only_over: Voxel,
+}
+
+impl PendingWrite {
+    pub fn new(pos: ChunkLocalVoxelPos, voxel: Voxel, only_over: Voxel) -> Self {
+        Self {
+            idx: ChunkData::linearize(pos.x, pos.y, pos.z) as u16,
+            voxel,
+            only_over,
+        }
+    }
+
+    pub fn local_pos(&self) -> ChunkLocalVoxelPos {
+        let (x, y, z) = ChunkData::delinearize(self.idx as usize);
+        ChunkLocalVoxelPos::new(x, y, z)
+    }
+
+    pub fn voxel(&self) -> Voxel {
+        self.voxel
+    }
+
+    /// Returns true if the chunk was modified
+    pub fn apply(&self, chunk: &mut ChunkData) -> bool {
+        let pos = self.local_pos();
+        if chunk.get(pos.x, pos.y, pos.z) != self.only_over {
+            return false;
+        }
+
+        chunk.set(pos.x, pos.y, pos.z, self.voxel);
+        true
+    }
+}
+
+/// Writes waiting for their chunk to be generated or loaded, saved with the world
+#[derive(Resource, Default)]
+pub struct PendingWrites {
+    writes: HashMap<ChunkPos, Vec<PendingWrite>>,
+    dirty: bool,
+}
+
+impl PendingWrites {
+    pub fn new(writes: HashMap<ChunkPos, Vec<PendingWrite>>) -> Self {
+        Self {
+            writes,
+            dirty: false,
+        }
+    }
+
+    pub fn push(&mut self, pos: ChunkPos, write: PendingWrite) {
+        self.writes.entry(pos).or_default().push(write);
+        self.dirty = true;
+    }
+
+    pub fn take(&mut self, pos: ChunkPos) -> Option<Vec<PendingWrite>> {
+        let writes = self.writes.remove(&pos);
+        if writes.is_some() {
+            self.dirty = true;
+        }
+        writes
+    }
+
+    pub fn writes(&self) -> &HashMap<ChunkPos, Vec<PendingWrite>> {
+        &self.writes
+    }
+
+    /// Whether the writes changed since they were last saved
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+}
+
+/// Apply writes to a chunk whose lighting is already computed.
+/// Returns the events for changed voxels, so lighting is updated the same way as player edits.
+pub fn apply_to_lit_chunk(
+    pos: ChunkPos,
+    data: &mut ChunkData,
+    writes: &[PendingWrite],
+) -> Vec<VoxelAddedEvent> {
+    writes
+        .iter()
+        .filter(|write| write.apply(data))
+        .map(|write| {
+            VoxelAddedEvent::new(
+                GlobalVoxelPos::from_chunk_local(pos, write.local_pos()),
+                write.voxel(),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use super::{ChunkData, ChunkPos, LoadedChunks};
+
+/// Extra data attached to a single voxel, such as the contents of a chest
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockEntity {
+    pub kind: String,
+    pub properties: BTreeMap<String, String>,
+}
+
+impl BlockEntity {
+    pub fn new(kind: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            properties: BTreeMap::new(),
+        }
+    }
+}
+
+/// Entity saved with the chunk it stands in, and spawned again when that chunk loads.
+/// Systems of each kind rebuild the rest of the entity when a `Persist` is added.
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+pub struct Persist {
+    pub kind: String,
+    /// State owned by the systems of this kind
+    pub data: Vec<u8>,
+}
+
+impl Persist {
+    pub fn new(kind: &str, data: Vec<u8>) -> Self {
+        Self {
+            kind: kind.to_string(),
+            data,
+        }
+    }
+}
+
+/// Persisted entity as stored in its chunk
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedEntity {
+    persist: Persist,
+    translation: [f32; 3],
+    rotation: [f32; 4],
+    scale: [f32; 3],
+}
+
+impl SavedEntity {
+    pub fn new(persist: &Persist, transform: &Transform) -> Self {
+        Self {
+            persist: persist.clone(),
+            translation: transform.translation.to_array(),
+            rotation: transform.rotation.to_array(),
+            scale: transform.scale.to_array(),
+        }
+    }
+
+    pub fn spawn(self, commands: &mut Commands) -> Entity {
+        commands
+            .spawn((
+                self.persist,
+                SpatialBundle::from_transform(Transform {
+                    translation: Vec3::from_array(self.translation),
+                    rotation: Quat::from_array(self.rotation),
+                    scale: Vec3::from_array(self.scale),
+                }),
+            ))
+            .id()
+    }
+}
+
+/// Chunk each persisted entity was last seen in,
+/// so chunks an entity leaves or disappears from get saved too
+#[derive(Resource, Default)]
+pub struct PersistedEntities(HashMap<Entity, ChunkPos>);
+
+/// Mark chunks dirty when the persisted entities inside them change, cross into another chunk or despawn.
+/// Moves within a chunk are saved along with its next change.
+pub(super) fn track_persisted_entities(
+    world: Res<LoadedChunks>,
+    mut persisted_entities: ResMut<PersistedEntities>,
+    changed: Query<(Entity, &Transform, Ref<Persist>), Or<(Changed<Transform>, Changed<Persist>)>>,
+    mut removed: RemovedComponents<Persist>,
+    mut chunks: Query<&mut ChunkData>,
+) {
+    let mut touched = Vec::new();
+    for (entity, transform, persist) in changed.iter() {
+        let pos = ChunkPos::from_global_coords(transform.translation);
+        let previous = persisted_entities.0.insert(entity, pos);
+        // Entities spawned with their chunk are already saved in it
+        if persist.is_added() {
+            continue;
+        }
+        if previous != Some(pos) {
+            touched.extend(previous);
+            touched.push(pos);
+        } else if persist.is_changed() {
+            touched.push(pos);
+        }
+    }
+    for entity in removed.read() {
+        if let Some(previous) = persisted_entities.0.remove(&entity) {
+            touched.push(previous);
+        }
+    }
+
+    for pos in touched {
+        if let Some(mut data) = world
+            .get_chunk(pos)
+            .and_then(|entity| chunks.get_mut(*entity).ok())
+        {
+            data.set_dirty(true);
+        }
+    }
+}
+
+/// Persisted entities grouped by the chunk they stand in
+pub fn saved_entities_by_chunk(
+    persisted: &Query<(Entity, &Persist, &Transform)>,
+) -> HashMap<ChunkPos, Vec<(Entity, SavedEntity)>> {
+    let mut by_chunk: HashMap<ChunkPos, Vec<(Entity, SavedEntity)>> = HashMap::new();
+    for (entity, persist, transform) in persisted.iter() {
+        by_chunk
+            .entry(ChunkPos::from_global_coords(transform.translation))
+            .or_default()
+            .push((entity, SavedEntity::new(persist, transform)));
+    }
+    by_chunk
+}
+
+/// Where the player stood and looked when the world was last saved
+#[derive(Component, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerState {
+    pub position: [f32; 3],
+    pub phi: f32,
+    pub theta: f32,
+}
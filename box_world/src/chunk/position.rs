@@ -59,7 +59,6 @@ impl ChunkPos {
         ]
     }
 
-    #[allow(dead_code)]
     pub fn distance(&self, other: &ChunkPos) -> f32 {
         self.as_vec3().distance(other.as_vec3())
     }
@@ -0,0 +1,82 @@
+use bevy::{
+    math::{Affine3A, Vec3A},
+    prelude::*,
+    render::primitives::{Aabb, Frustum},
+};
+
+use super::{ChunkData, ChunkPos, LoadPoint};
+
+/// Chunks inside a camera frustum count as this many times closer
+const FRUSTUM_WEIGHT: f32 = 3.0;
+
+/// Where the chunks that matter most are, refreshed every frame
+/// so queued work follows the load points as they move and turn
+#[derive(Resource, Default)]
+pub struct ChunkPriorities {
+    load_points: Vec<ChunkPos>,
+    frusta: Vec<Frustum>,
+}
+
+impl ChunkPriorities {
+    /// Distance in chunks to the nearest load point, shrunk for chunks in view.
+    /// Lower is more urgent.
+    pub fn priority(&self, pos: ChunkPos) -> f32 {
+        let distance = self
+            .load_points
+            .iter()
+            .map(|load_point| load_point.distance(&pos))
+            .fold(f32::INFINITY, f32::min);
+        if !distance.is_finite() {
+            return 0.0;
+        }
+
+        if self.in_view(pos) {
+            distance / FRUSTUM_WEIGHT
+        } else {
+            distance
+        }
+    }
+
+    fn in_view(&self, pos: ChunkPos) -> bool {
+        let half_edge = ChunkData::edge() as f32 / 2.0;
+        let aabb = Aabb {
+            center: Vec3A::splat(half_edge),
+            half_extents: Vec3A::splat(half_edge),
+        };
+        let chunk_to_world = Affine3A::from_translation(pos.to_global_coords());
+        self.frusta
+            .iter()
+            .any(|frustum| frustum.intersects_obb(&aabb, &chunk_to_world, true, false))
+    }
+
+    /// The `count` most urgent items, most urgent first
+    pub fn most_urgent<T>(
+        &self,
+        items: impl Iterator<Item = (ChunkPos, T)>,
+        count: usize,
+    ) -> Vec<T> {
+        let mut ranked: Vec<(f32, T)> = items
+            .map(|(pos, item)| (self.priority(pos), item))
+            .collect();
+        let by_priority = |a: &(f32, T), b: &(f32, T)| a.0.total_cmp(&b.0);
+
+        if ranked.len() > count {
+            ranked.select_nth_unstable_by(count, by_priority);
+            ranked.truncate(count);
+        }
+        ranked.sort_unstable_by(by_priority);
+        ranked.into_iter().map(|(_priority, item)| item).collect()
+    }
+}
+
+pub(super) fn update_chunk_priorities(
+    mut priorities: ResMut<ChunkPriorities>,
+    load_points: Query<&Transform, With<LoadPoint>>,
+    cameras: Query<&Frustum, With<Camera3d>>,
+) {
+    priorities.load_points = load_points
+        .iter()
+        .map(|transform| ChunkPos::from_global_coords(transform.translation))
+        .collect();
+    priorities.frusta = cameras.iter().copied().collect();
+}
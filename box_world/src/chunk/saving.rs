@@ -0,0 +1,784 @@
+use std::{
+    io, panic,
+    sync::{Arc, Mutex, MutexGuard, Once, TryLockError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    app::AppExit,
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::{HashMap, HashSet},
+};
+use futures_lite::future;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+
+use super::{
+    data::RawChunk,
+    database,
+    format::ChunkCompression,
+    pending::PendingWrite,
+    persist::{saved_entities_by_chunk, Persist, PlayerState, SavedEntity},
+    store::ChunkStore,
+    ChunkData, ChunkPos, Database, LoadedChunks, PendingWrites,
+};
+
+/// Seconds between two save passes
+const SAVE_INTERVAL: f32 = 5.0;
+/// Chunks snapshotted in a single frame, bigger passes carry on over the next frames
+const MAX_SNAPSHOTS_PER_FRAME: usize = 5000;
+/// Attempts at writing everything left when the game closes
+const FLUSH_ATTEMPTS: usize = 3;
+/// How long the panic hook waits for a lock another thread holds
+const PANIC_LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+/// Journal tag of changes snapshotted as they happen, not part of any save yet.
+/// Their chunks are still dirty, so the next pass saves them like any other.
+const LIVE: u64 = u64::MAX;
+
+/// Journal of the open world and where to write it, used by the panic hook
+static PANIC_SAVE: Mutex<Option<(Arc<Mutex<Journal>>, SaveTarget)>> = Mutex::new(None);
+static PANIC_HOOK: Once = Once::new();
+
+/// Everything changed or handed to a save but not confirmed on disk yet, tagged with the batch holding it.
+/// Shared with the panic hook, so it can still be written if the game goes down.
+#[derive(Default)]
+struct Journal {
+    chunks: HashMap<ChunkPos, (u64, Arc<RawChunk>)>,
+    pending_writes: Option<(u64, Arc<Vec<(ChunkPos, Vec<PendingWrite>)>>)>,
+    player: Option<(u64, PlayerState)>,
+}
+
+impl Journal {
+    fn confirm(&mut self, batch: &SaveBatch) {
+        for (pos, _raw) in batch.chunks.iter() {
+            if matches!(self.chunks.get(pos), Some((id, _raw)) if *id == batch.id) {
+                self.chunks.remove(pos);
+            }
+        }
+        if matches!(self.pending_writes, Some((id, _)) if id == batch.id) {
+            self.pending_writes = None;
+        }
+        if matches!(self.player, Some((id, _)) if id == batch.id) {
+            self.player = None;
+        }
+    }
+
+    /// Write everything in the journal at once, emptying it on success
+    fn flush(&mut self, target: &SaveTarget) -> io::Result<usize> {
+        let batch = SaveBatch {
+            id: 0,
+            chunks: self
+                .chunks
+                .iter()
+                .map(|(pos, (_id, raw))| (*pos, raw.clone()))
+                .collect(),
+            revisions: Vec::new(),
+            pending_writes: self
+                .pending_writes
+                .as_ref()
+                .map(|(_id, writes)| writes.clone()),
+            player: self.player.map(|(_id, player)| player),
+        };
+        target.write(&batch)?;
+
+        *self = Journal::default();
+        Ok(batch.chunks.len())
+    }
+}
+
+/// Where saves are written to
+#[derive(Clone)]
+struct SaveTarget {
+    chunk_store: Arc<dyn ChunkStore>,
+    compression: ChunkCompression,
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl SaveTarget {
+    fn new(database: &Database) -> Self {
+        Self {
+            chunk_store: database.get_chunk_store(),
+            compression: database.get_compression(),
+            pool: database.get_connection_pool(),
+        }
+    }
+
+    fn write(&self, batch: &SaveBatch) -> io::Result<()> {
+        if !batch.chunks.is_empty() {
+            database::save_raw_chunks(self.chunk_store.as_ref(), &self.compression, &batch.chunks)?;
+        }
+        if let Some(writes) = &batch.pending_writes {
+            database::save_pending_writes(&self.pool, writes)?;
+        }
+        if let Some(player) = &batch.player {
+            database::save_player(&self.pool, player)?;
+        }
+        Ok(())
+    }
+}
+
+/// Data written by a single save task
+#[derive(Default)]
+struct SaveBatch {
+    id: u64,
+    chunks: Vec<(ChunkPos, Arc<RawChunk>)>,
+    /// Loaded chunks to mark as saved once the batch lands
+    revisions: Vec<(ChunkPos, Entity, u32)>,
+    pending_writes: Option<Arc<Vec<(ChunkPos, Vec<PendingWrite>)>>>,
+    player: Option<PlayerState>,
+}
+
+impl SaveBatch {
+    fn is_empty(&self) -> bool {
+        self.chunks.is_empty() && self.pending_writes.is_none() && self.player.is_none()
+    }
+}
+
+/// Tracks every save from the snapshot to the confirmed write.
+/// Chunks stay dirty until the write of their latest revision is confirmed,
+/// failed saves are retried on the next pass and whatever is left is written on close or panic.
+#[derive(Resource)]
+pub struct SaveCoordinator {
+    journal: Arc<Mutex<Journal>>,
+    target: SaveTarget,
+    /// Chunks with a save task running, they are not snapshotted again until it finishes
+    in_flight: HashSet<ChunkPos>,
+    tasks: Vec<Task<(SaveBatch, io::Result<()>)>>,
+    next_batch_id: u64,
+    timer: Timer,
+    /// The last pass hit the snapshot limit and continues on the next frame
+    pass_running: bool,
+    /// Revision of each dirty chunk last copied to the journal
+    journaled: HashMap<ChunkPos, u32>,
+    /// Player state handed to the last save, it is only written again once it changes
+    saved_player: Option<PlayerState>,
+}
+
+impl SaveCoordinator {
+    pub fn new(database: &Database) -> Self {
+        let coordinator = Self {
+            journal: Arc::new(Mutex::new(Journal::default())),
+            target: SaveTarget::new(database),
+            in_flight: HashSet::new(),
+            tasks: Vec::new(),
+            next_batch_id: 1,
+            timer: Timer::from_seconds(SAVE_INTERVAL, TimerMode::Repeating),
+            pass_running: false,
+            journaled: HashMap::new(),
+            saved_player: None,
+        };
+        coordinator.watch_for_panics();
+        coordinator
+    }
+
+    /// Point the panic hook at this journal, installing the hook on first use.
+    /// The hook lives as long as the process, later coordinators only swap the journal it writes.
+    fn watch_for_panics(&self) {
+        PANIC_HOOK.call_once(|| {
+            let previous_hook = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                previous_hook(info);
+                flush_after_panic();
+            }));
+        });
+
+        *lock_ignoring_poison(&PANIC_SAVE) = Some((self.journal.clone(), self.target.clone()));
+    }
+
+    fn journal(&self) -> MutexGuard<'_, Journal> {
+        lock_journal(&self.journal)
+    }
+
+    fn new_batch(&mut self) -> SaveBatch {
+        let id = self.next_batch_id;
+        self.next_batch_id += 1;
+        SaveBatch { id, ..default() }
+    }
+
+    /// Latest data of a chunk whose save hasn't landed yet, newer than what the store holds
+    pub fn unsaved_chunk(&self, pos: ChunkPos) -> Option<Arc<RawChunk>> {
+        self.journal()
+            .chunks
+            .get(&pos)
+            .map(|(_id, raw)| raw.clone())
+    }
+
+    pub fn is_in_flight(&self, pos: ChunkPos) -> bool {
+        self.in_flight.contains(&pos)
+    }
+
+    /// Save chunks that were just unloaded, their data only lives here until the write lands
+    pub fn save_unloaded(&mut self, chunks: Vec<(ChunkPos, RawChunk)>) {
+        let mut batch = self.new_batch();
+        let mut journal = lock_journal(&self.journal);
+        for (pos, raw) in chunks {
+            self.journaled.remove(&pos);
+            let raw = Arc::new(raw);
+            if self.in_flight.contains(&pos) {
+                // Journaled under its own id so the running save doesn't confirm it,
+                // it is written by a later pass once that save is done
+                journal.chunks.insert(pos, (0, raw));
+            } else {
+                journal.chunks.insert(pos, (batch.id, raw.clone()));
+                batch.chunks.push((pos, raw));
+            }
+        }
+        drop(journal);
+
+        self.spawn(batch);
+    }
+
+    /// Add journaled chunks that aren't being written, left behind by failed or overlapping saves
+    fn add_retries(&self, batch: &mut SaveBatch) {
+        let mut journal = self.journal();
+        let queued: HashSet<ChunkPos> = batch.chunks.iter().map(|(pos, _raw)| *pos).collect();
+        for (pos, (id, raw)) in journal.chunks.iter_mut() {
+            // Live snapshots belong to dirty chunks the pass snapshots itself
+            if *id == LIVE || self.in_flight.contains(pos) || queued.contains(pos) {
+                continue;
+            }
+            *id = batch.id;
+            batch.chunks.push((*pos, raw.clone()));
+        }
+    }
+
+    fn spawn(&mut self, batch: SaveBatch) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let mut journal = lock_journal(&self.journal);
+        for (pos, raw) in batch.chunks.iter() {
+            journal.chunks.insert(*pos, (batch.id, raw.clone()));
+            self.in_flight.insert(*pos);
+        }
+        if let Some(writes) = &batch.pending_writes {
+            journal.pending_writes = Some((batch.id, writes.clone()));
+        }
+        if let Some(player) = batch.player {
+            journal.player = Some((batch.id, player));
+        }
+        drop(journal);
+
+        let target = self.target.clone();
+        self.tasks
+            .push(AsyncComputeTaskPool::get().spawn(async move {
+                let result = target.write(&batch);
+                (batch, result)
+            }));
+    }
+}
+
+impl Drop for SaveCoordinator {
+    fn drop(&mut self) {
+        let mut panic_save = lock_ignoring_poison(&PANIC_SAVE);
+        if matches!(&*panic_save, Some((journal, _target)) if Arc::ptr_eq(journal, &self.journal)) {
+            *panic_save = None;
+        }
+    }
+}
+
+/// Write the journal of the open world when any thread panics,
+/// the game can't be trusted to reach its exit.
+/// Every change is journaled by the end of the frame it happens in, so only the frame that panicked is lost.
+fn flush_after_panic() {
+    // The panic may have happened while either lock was held on this very thread
+    let Some(panic_save) = lock_for_panic(&PANIC_SAVE) else {
+        eprintln!("Save journal is being replaced, unsaved chunks are lost");
+        return;
+    };
+    let Some((journal, target)) = panic_save.as_ref() else {
+        return;
+    };
+
+    let Some(mut journal) = lock_for_panic(journal) else {
+        eprintln!("Save journal is locked, unsaved chunks are lost");
+        return;
+    };
+    match journal.flush(target) {
+        Ok(count) => eprintln!("Flushed {} chunks after a panic", count),
+        Err(err) => eprintln!("Failed to flush chunks after a panic: {}", err),
+    }
+}
+
+/// Wait for another thread to release the lock, giving up if it is held by the panicking one
+fn lock_for_panic<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    let start = Instant::now();
+    loop {
+        match mutex.try_lock() {
+            Ok(guard) => return Some(guard),
+            Err(TryLockError::Poisoned(poisoned)) => return Some(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) if start.elapsed() < PANIC_LOCK_TIMEOUT => {
+                thread::sleep(Duration::from_millis(1));
+            }
+            Err(TryLockError::WouldBlock) => return None,
+        }
+    }
+}
+
+/// Snapshot dirty chunks, pending writes and the player, then write them on a worker thread
+pub(super) fn save_dirty_chunks(
+    time: Res<Time>,
+    mut coordinator: ResMut<SaveCoordinator>,
+    mut pending_writes: ResMut<PendingWrites>,
+    chunks: Query<(Entity, &ChunkPos, &ChunkData)>,
+    persisted: Query<(Entity, &Persist, &Transform)>,
+    player: Query<&PlayerState>,
+) {
+    if coordinator.timer.tick(time.delta()).just_finished() {
+        coordinator.pass_running = true;
+    }
+    if !coordinator.pass_running {
+        return;
+    }
+
+    let mut saved_entities = saved_entities_by_chunk(&persisted);
+    let mut batch = coordinator.new_batch();
+    for (entity, pos, data) in chunks
+        .iter()
+        .filter(|(_entity, pos, data)| data.is_dirty() && !coordinator.is_in_flight(**pos))
+        .take(MAX_SNAPSHOTS_PER_FRAME)
+    {
+        let raw = data.to_raw(take_saved_entities(&mut saved_entities, *pos));
+        batch.chunks.push((*pos, Arc::new(raw)));
+        batch.revisions.push((*pos, entity, data.revision()));
+        coordinator.journaled.insert(*pos, data.revision());
+    }
+    coordinator.pass_running = batch.chunks.len() == MAX_SNAPSHOTS_PER_FRAME;
+    coordinator.add_retries(&mut batch);
+
+    if pending_writes.is_dirty() {
+        batch.pending_writes = Some(Arc::new(clone_writes(&pending_writes)));
+        pending_writes.set_dirty(false);
+    }
+    if let Ok(player) = player.get_single() {
+        if coordinator.saved_player != Some(*player) {
+            coordinator.saved_player = Some(*player);
+            batch.player = Some(*player);
+        }
+    }
+
+    if !batch.chunks.is_empty() {
+        info!("Saving {} chunks", batch.chunks.len());
+    }
+    coordinator.spawn(batch);
+}
+
+/// Copy chunks changed this frame to the journal, along with pending writes and the player,
+/// so a panic can write every change that isn't on disk yet
+pub(super) fn journal_changes(
+    mut coordinator: ResMut<SaveCoordinator>,
+    pending_writes: Res<PendingWrites>,
+    chunks: Query<(&ChunkPos, &ChunkData), Changed<ChunkData>>,
+    persisted: Query<(Entity, &Persist, &Transform)>,
+    player: Query<&PlayerState, Changed<PlayerState>>,
+) {
+    // Lighting and trimming change chunks without a new revision, there is nothing new to save
+    let changed: Vec<(&ChunkPos, &ChunkData)> = chunks
+        .iter()
+        .filter(|(pos, data)| {
+            data.is_dirty() && coordinator.journaled.get(*pos) != Some(&data.revision())
+        })
+        .collect();
+
+    let mut snapshots = Vec::with_capacity(changed.len());
+    if !changed.is_empty() {
+        let mut saved_entities = saved_entities_by_chunk(&persisted);
+        for (pos, data) in changed {
+            let raw = data.to_raw(take_saved_entities(&mut saved_entities, *pos));
+            coordinator.journaled.insert(*pos, data.revision());
+            snapshots.push((*pos, Arc::new(raw)));
+        }
+    }
+
+    let mut journal = coordinator.journal();
+    for (pos, raw) in snapshots {
+        journal.chunks.insert(pos, (LIVE, raw));
+    }
+    if pending_writes.is_changed() && pending_writes.is_dirty() {
+        journal.pending_writes = Some((LIVE, Arc::new(clone_writes(&pending_writes))));
+    }
+    if let Ok(player) = player.get_single() {
+        journal.player = Some((LIVE, *player));
+    }
+}
+
+/// Confirm finished saves, chunks of failed ones stay dirty and are saved again on the next pass
+pub(super) fn handle_done_save_tasks(
+    world: Res<LoadedChunks>,
+    mut coordinator: ResMut<SaveCoordinator>,
+    mut pending_writes: ResMut<PendingWrites>,
+    mut chunks: Query<&mut ChunkData>,
+) {
+    let mut done = Vec::new();
+    coordinator
+        .tasks
+        .retain_mut(|task| match future::block_on(future::poll_once(task)) {
+            Some(outcome) => {
+                done.push(outcome);
+                false
+            }
+            None => true,
+        });
+
+    for (batch, result) in done {
+        for (pos, _raw) in batch.chunks.iter() {
+            coordinator.in_flight.remove(pos);
+        }
+
+        if let Err(err) = result {
+            error!(
+                "Failed to save {} chunks, will retry: {}",
+                batch.chunks.len(),
+                err
+            );
+            if batch.pending_writes.is_some() {
+                pending_writes.set_dirty(true);
+            }
+            if batch.player.is_some() {
+                coordinator.saved_player = None;
+            }
+            continue;
+        }
+
+        coordinator.journal().confirm(&batch);
+        for (pos, entity, revision) in batch.revisions {
+            // The chunk may have been unloaded and loaded again since the snapshot
+            if world.get_chunk(pos) != Some(&entity) {
+                continue;
+            }
+            if let Ok(mut data) = chunks.get_mut(entity) {
+                data.mark_saved(revision);
+                if !data.is_dirty() {
+                    coordinator.journaled.remove(&pos);
+                }
+            }
+        }
+    }
+}
+
+/// Wait for running saves, then write every dirty chunk before the game closes
+pub(super) fn flush_on_exit(
+    exit: EventReader<AppExit>,
+    mut coordinator: ResMut<SaveCoordinator>,
+    pending_writes: Res<PendingWrites>,
+    chunks: Query<(&ChunkPos, &ChunkData)>,
+    persisted: Query<(Entity, &Persist, &Transform)>,
+    player: Query<&PlayerState>,
+) {
+    if exit.is_empty() {
+        return;
+    }
+    info!("Save on close");
+
+    // Running saves land first so they can't overwrite newer data written here.
+    // Their chunks are still journaled, so failed ones are written again below.
+    for task in coordinator.tasks.drain(..) {
+        future::block_on(task);
+    }
+    coordinator.in_flight.clear();
+
+    let mut saved_entities = saved_entities_by_chunk(&persisted);
+    let mut journal = coordinator.journal();
+    for (pos, data) in chunks.iter().filter(|(_pos, data)| data.is_dirty()) {
+        let raw = data.to_raw(take_saved_entities(&mut saved_entities, *pos));
+        journal.chunks.insert(*pos, (0, Arc::new(raw)));
+    }
+    if pending_writes.is_dirty() {
+        journal.pending_writes = Some((0, Arc::new(clone_writes(&pending_writes))));
+    }
+    if let Ok(player) = player.get_single() {
+        journal.player = Some((0, *player));
+    }
+
+    for attempt in 1..=FLUSH_ATTEMPTS {
+        match journal.flush(&coordinator.target) {
+            Ok(count) => {
+                info!("Saved {} chunks on close", count);
+                return;
+            }
+            Err(err) => error!(
+                "Failed to save on close, attempt {}/{}: {}",
+                attempt, FLUSH_ATTEMPTS, err
+            ),
+        }
+    }
+}
+
+fn take_saved_entities(
+    saved_entities: &mut HashMap<ChunkPos, Vec<(Entity, SavedEntity)>>,
+    pos: ChunkPos,
+) -> Vec<SavedEntity> {
+    saved_entities
+        .remove(&pos)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(_entity, saved)| saved)
+        .collect()
+}
+
+fn lock_journal(journal: &Mutex<Journal>) -> MutexGuard<'_, Journal> {
+    // A panic while holding the lock leaves the journal as it was, still worth writing
+    lock_ignoring_poison(journal)
+}
+
+fn lock_ignoring_poison<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn clone_writes(pending_writes: &PendingWrites) -> Vec<(ChunkPos, Vec<PendingWrite>)> {
+    pending_writes
+        .writes()
+        .iter()
+        .map(|(pos, writes)| (*pos, writes.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+        process::{self, Command},
+    };
+
+    use rand::Rng;
+
+    use super::*;
+    use crate::{
+        chunk::{format::ChunkCodec, meta::DEFAULT_GENERATOR, ChunkStoreKind, WorldMeta, Worlds},
+        voxel::Voxel,
+    };
+
+    /// Saves directory of the world a child process writes to, only set in the child
+    const CHILD_SAVES: &str = "BOX_WORLD_CRASH_SAVES";
+    const WORLD: &str = "crash";
+    /// Created by the child once a first save is confirmed
+    const STARTED: &str = "started";
+    /// Round of each chunk as of its last confirmed save, rewritten by the child after every confirmation
+    const CONFIRMED: &str = "confirmed";
+    /// Chunks along each axis of the saved area
+    const AREA_EDGE: i32 = 4;
+    /// Round after which the child stops, in case nothing kills it. Rounds must fit in a voxel id.
+    const MAX_ROUND: u16 = 2000;
+    const KILLS: usize = 5;
+
+    fn positions() -> Vec<ChunkPos> {
+        let mut positions = Vec::new();
+        for x in 0..AREA_EDGE {
+            for y in 0..AREA_EDGE {
+                for z in 0..AREA_EDGE {
+                    positions.push(ChunkPos::new(x, y, z));
+                }
+            }
+        }
+        positions
+    }
+
+    /// Chunk as edited in a round, the voxel at the origin holds the round.
+    /// The number of voxel types changes with the round so blobs change size between rounds.
+    fn round_chunk(round: u16) -> ChunkData {
+        let kinds = 1 + round % 5;
+        let mut data = ChunkData::default();
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    let offset = (x * 7 + y * 3 + z) as u16 % kinds;
+                    data.set(x, y, z, Voxel::new(round + offset));
+                }
+            }
+        }
+        data
+    }
+
+    /// Round of every chunk on disk, each must be readable and hold one whole round
+    fn stored_rounds(saves: &Path) -> HashMap<ChunkPos, u16> {
+        let world = Worlds::new(saves).open(WORLD).unwrap();
+        let database = Database::open(&world);
+        let compression = database.get_compression();
+
+        let mut rounds = HashMap::new();
+        for (pos, blob) in database.get_chunk_store().load_blobs(&positions()) {
+            let Some(blob) = blob.unwrap() else {
+                continue;
+            };
+            let (data, _entities) = compression
+                .decode(&blob)
+                .unwrap_or_else(|err| panic!("Chunk {:?} is corrupt: {}", pos, err));
+
+            let round = data.get(0, 0, 0).id();
+            let expected = round_chunk(round);
+            for x in 0..16 {
+                for y in 0..16 {
+                    for z in 0..16 {
+                        assert_eq!(
+                            data.get(x, y, z),
+                            expected.get(x, y, z),
+                            "Chunk {:?} mixes two saves",
+                            pos
+                        );
+                    }
+                }
+            }
+            rounds.insert(pos, round);
+        }
+        rounds
+    }
+
+    /// Edit random chunks of the area every frame and save them through the coordinator,
+    /// recording the round of each save it confirms
+    fn save_until_killed(saves: &Path) -> ! {
+        let mut rounds = stored_rounds(saves);
+        let world = Worlds::new(saves).open(WORLD).unwrap();
+        let database = Database::open(&world);
+
+        let mut app = App::new();
+        app.add_plugins(TaskPoolPlugin::default())
+            .insert_resource(Time::<()>::default())
+            .insert_resource(SaveCoordinator::new(&database))
+            .insert_resource(PendingWrites::default())
+            .insert_resource(LoadedChunks::new())
+            .add_systems(Update, save_dirty_chunks)
+            .add_systems(PostUpdate, handle_done_save_tasks);
+
+        // Round edited into each chunk entity, and the round of its last confirmed save
+        let mut chunks = HashMap::new();
+        for pos in positions() {
+            let round = rounds.get(&pos).copied().unwrap_or(1);
+            let entity = app.world.spawn((pos, round_chunk(round))).id();
+            chunks.insert(entity, (pos, round));
+        }
+        rounds.clear();
+
+        let mut rng = rand::thread_rng();
+        loop {
+            // Every frame runs a save pass
+            app.world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(SAVE_INTERVAL));
+            app.update();
+
+            let mut confirmed = false;
+            for (entity, (pos, round)) in chunks.iter_mut() {
+                let mut data = app.world.get_mut::<ChunkData>(*entity).unwrap();
+                if !data.is_dirty() && rounds.get(pos) != Some(round) {
+                    rounds.insert(*pos, *round);
+                    confirmed = true;
+                }
+                if rng.gen_bool(0.5) {
+                    *round += 1;
+                    if *round > MAX_ROUND {
+                        process::exit(0);
+                    }
+                    // Edited in place, a fresh chunk would restart its revisions
+                    let edited = round_chunk(*round);
+                    for x in 0..16 {
+                        for y in 0..16 {
+                            for z in 0..16 {
+                                data.set(x, y, z, edited.get(x, y, z));
+                            }
+                        }
+                    }
+                }
+            }
+
+            if confirmed {
+                // Renamed into place so a kill never leaves half a record
+                let record: String = rounds
+                    .iter()
+                    .map(|(pos, round)| format!("{} {} {} {}\n", pos.x, pos.y, pos.z, round))
+                    .collect();
+                fs::write(saves.join("confirmed.tmp"), record).unwrap();
+                fs::rename(saves.join("confirmed.tmp"), saves.join(CONFIRMED)).unwrap();
+                if !saves.join(STARTED).exists() {
+                    fs::write(saves.join(STARTED), "").unwrap();
+                }
+            }
+        }
+    }
+
+    /// Every chunk the child confirmed must be on disk with at least the confirmed round
+    fn check_chunks(saves: &Path) {
+        let stored = stored_rounds(saves);
+        let confirmed = fs::read_to_string(saves.join(CONFIRMED)).unwrap();
+        for line in confirmed.lines() {
+            let fields: Vec<i32> = line
+                .split(' ')
+                .map(|field| field.parse().unwrap())
+                .collect();
+            let pos = ChunkPos::new(fields[0], fields[1], fields[2]);
+            let round = fields[3] as u16;
+
+            let stored = stored
+                .get(&pos)
+                .unwrap_or_else(|| panic!("Confirmed chunk {:?} is missing", pos));
+            assert!(
+                *stored >= round,
+                "Chunk {:?} holds round {} but round {} was confirmed",
+                pos,
+                stored,
+                round
+            );
+        }
+    }
+
+    /// Kill a child process in the middle of its saves several times, checking the world after each
+    fn survive_kills(test_name: &str, chunk_store: ChunkStoreKind) {
+        if let Ok(saves) = env::var(CHILD_SAVES) {
+            save_until_killed(Path::new(&saves));
+        }
+
+        let saves: PathBuf =
+            env::temp_dir().join(format!("box_world_{}_{}", test_name, process::id()));
+        let _ = fs::remove_dir_all(&saves);
+        let meta = WorldMeta {
+            chunk_store,
+            // Uncompressed blobs span several region sectors
+            chunk_codec: ChunkCodec::None,
+            ..WorldMeta::new(0, DEFAULT_GENERATOR)
+        };
+        Worlds::new(&saves).create(WORLD, &meta).unwrap();
+
+        for _ in 0..KILLS {
+            let _ = fs::remove_file(saves.join(STARTED));
+            let _ = fs::remove_file(saves.join(CONFIRMED));
+            let mut child = Command::new(env::current_exe().unwrap())
+                .arg(format!("chunk::saving::tests::{}", test_name))
+                .args(["--exact", "--nocapture"])
+                .env(CHILD_SAVES, &saves)
+                .spawn()
+                .unwrap();
+
+            let started = Instant::now();
+            while !saves.join(STARTED).exists() {
+                assert!(
+                    started.elapsed() < Duration::from_secs(30),
+                    "Child never confirmed a save"
+                );
+                thread::sleep(Duration::from_millis(5));
+            }
+            thread::sleep(Duration::from_millis(rand::thread_rng().gen_range(0..100)));
+            // The child may have already stopped at its last round
+            let _ = child.kill();
+            child.wait().unwrap();
+
+            check_chunks(&saves);
+        }
+
+        fs::remove_dir_all(&saves).unwrap();
+    }
+
+    #[test]
+    fn sqlite_survives_kill_during_save() {
+        survive_kills("sqlite_survives_kill_during_save", ChunkStoreKind::Sqlite);
+    }
+
+    #[test]
+    fn region_survives_kill_during_save() {
+        survive_kills("region_survives_kill_during_save", ChunkStoreKind::Region);
+    }
+}
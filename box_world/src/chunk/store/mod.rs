@@ -0,0 +1,78 @@
+use std::{fmt, io, str::FromStr};
+
+mod region;
+mod sqlite;
+
+pub use region::RegionChunkStore;
+pub use sqlite::SqliteChunkStore;
+
+use super::ChunkPos;
+
+/// Backend holding the encoded blobs of saved chunks
+pub trait ChunkStore: Send + Sync {
+    /// Stored blobs of the given chunks, None for chunks that were never saved
+    fn load_blobs(&self, positions: &[ChunkPos]) -> Vec<(ChunkPos, io::Result<Option<Vec<u8>>>)>;
+
+    /// Store blobs, replacing any previous version of the same chunks
+    fn save_blobs(&self, blobs: Vec<(ChunkPos, Vec<u8>)>) -> io::Result<()>;
+
+    /// Set aside an unreadable chunk so it can be inspected later and regenerated now
+    fn quarantine(&self, pos: ChunkPos, reason: &str) -> io::Result<()>;
+
+    /// Every stored chunk
+    fn positions(&self) -> io::Result<Vec<ChunkPos>>;
+
+    /// Remove every stored chunk
+    fn clear(&self) -> io::Result<()>;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkStoreKind {
+    /// One row per chunk in the world database
+    #[default]
+    Sqlite,
+    /// Region files next to the world database
+    Region,
+}
+
+impl fmt::Display for ChunkStoreKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkStoreKind::Sqlite => write!(f, "sqlite"),
+            ChunkStoreKind::Region => write!(f, "region"),
+        }
+    }
+}
+
+impl FromStr for ChunkStoreKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sqlite" => Ok(ChunkStoreKind::Sqlite),
+            "region" => Ok(ChunkStoreKind::Region),
+            other => Err(format!("Unknown chunk store {}", other)),
+        }
+    }
+}
+
+/// Copy every chunk from one store to another, a batch at a time
+pub fn copy_chunks(from: &dyn ChunkStore, to: &dyn ChunkStore) -> io::Result<usize> {
+    const BATCH_SIZE: usize = 256;
+
+    let positions = from.positions()?;
+    for batch in positions.chunks(BATCH_SIZE) {
+        let blobs = from
+            .load_blobs(batch)
+            .into_iter()
+            .filter_map(|(pos, blob)| match blob {
+                Ok(Some(blob)) => Some(Ok((pos, blob))),
+                Ok(None) => None,
+                Err(err) => Some(Err(err)),
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        to.save_blobs(blobs)?;
+    }
+
+    Ok(positions.len())
+}
@@ -0,0 +1,423 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    prelude::{info, info_span, IVec3},
+    utils::HashMap,
+};
+
+use super::ChunkStore;
+use crate::chunk::ChunkPos;
+
+/// Chunks along each axis of a region
+const REGION_EDGE: i32 = 32;
+const CHUNKS_PER_REGION: usize = (REGION_EDGE * REGION_EDGE * REGION_EDGE) as usize;
+const SECTOR_SIZE: u64 = 512;
+/// Each header entry holds the first sector and byte length of a chunk, as little endian u32s
+const ENTRY_SIZE: u64 = 8;
+const HEADER_SECTORS: u32 = (CHUNKS_PER_REGION as u64 * ENTRY_SIZE / SECTOR_SIZE) as u32;
+/// Compact a region once more than this share of its data sectors is unused
+const COMPACTION_THRESHOLD: f32 = 0.5;
+const REGION_EXTENSION: &str = "region";
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Entry {
+    /// First sector of the chunk, 0 if the chunk isn't stored since the header starts there
+    sector: u32,
+    length: u32,
+}
+
+impl Entry {
+    fn is_empty(&self) -> bool {
+        self.sector == 0
+    }
+
+    fn sectors(&self) -> u32 {
+        sectors_for(self.length as u64)
+    }
+}
+
+fn sectors_for(length: u64) -> u32 {
+    length.div_ceil(SECTOR_SIZE).max(1) as u32
+}
+
+/// A file holding a 32x32x32 block of chunks.
+/// It starts with a header of one entry per chunk, followed by chunk data aligned on sectors.
+struct RegionFile {
+    path: PathBuf,
+    file: File,
+    entries: Vec<Entry>,
+    /// Which sectors of the file are in use, including the header
+    used: Vec<bool>,
+    /// Sectors of replaced chunks, only reused once the new header entries reached the disk
+    released: Vec<Entry>,
+}
+
+impl RegionFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let header_len = HEADER_SECTORS as u64 * SECTOR_SIZE;
+        if file.metadata()?.len() < header_len {
+            file.set_len(header_len)?;
+        }
+
+        let mut header = vec![0; header_len as usize];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+        let entries: Vec<Entry> = header
+            .chunks_exact(ENTRY_SIZE as usize)
+            .map(|entry| Entry {
+                sector: u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+                length: u32::from_le_bytes(entry[4..8].try_into().unwrap()),
+            })
+            .collect();
+
+        let file_sectors = file.metadata()?.len().div_ceil(SECTOR_SIZE) as usize;
+        let mut used = vec![false; file_sectors];
+        used[..HEADER_SECTORS as usize].fill(true);
+        for entry in entries.iter().filter(|entry| !entry.is_empty()) {
+            let range = entry.sector as usize..(entry.sector + entry.sectors()) as usize;
+            if range.end > used.len() {
+                used.resize(range.end, false);
+            }
+            used[range].fill(true);
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            entries,
+            used,
+            released: Vec::new(),
+        })
+    }
+
+    fn read(&mut self, idx: usize) -> io::Result<Option<Vec<u8>>> {
+        let entry = self.entries[idx];
+        if entry.is_empty() {
+            return Ok(None);
+        }
+
+        let mut blob = vec![0; entry.length as usize];
+        self.file
+            .seek(SeekFrom::Start(entry.sector as u64 * SECTOR_SIZE))?;
+        self.file.read_exact(&mut blob)?;
+        Ok(Some(blob))
+    }
+
+    /// New data always goes to free sectors before the header points to it,
+    /// so an interrupted write leaves the previous version of the chunk intact.
+    /// The previous sectors stay reserved until the next `sync`.
+    fn write(&mut self, idx: usize, blob: &[u8]) -> io::Result<()> {
+        let sectors = sectors_for(blob.len() as u64);
+        let sector = self.allocate(sectors);
+
+        self.file
+            .seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE))?;
+        self.file.write_all(blob)?;
+
+        let previous = self.entries[idx];
+        self.set_entry(
+            idx,
+            Entry {
+                sector,
+                length: blob.len() as u32,
+            },
+        )?;
+        self.released.push(previous);
+        Ok(())
+    }
+
+    fn remove(&mut self, idx: usize) -> io::Result<()> {
+        let previous = self.entries[idx];
+        self.set_entry(idx, Entry::default())?;
+        self.released.push(previous);
+        Ok(())
+    }
+
+    /// Flush the writes to disk, then free the sectors they replaced.
+    /// Freeing them earlier would let a later write overwrite a chunk the header on disk still points to.
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        for entry in std::mem::take(&mut self.released) {
+            self.free(entry);
+        }
+        Ok(())
+    }
+
+    fn set_entry(&mut self, idx: usize, entry: Entry) -> io::Result<()> {
+        let mut bytes = [0; ENTRY_SIZE as usize];
+        bytes[0..4].copy_from_slice(&entry.sector.to_le_bytes());
+        bytes[4..8].copy_from_slice(&entry.length.to_le_bytes());
+
+        self.file.seek(SeekFrom::Start(idx as u64 * ENTRY_SIZE))?;
+        self.file.write_all(&bytes)?;
+        self.entries[idx] = entry;
+        Ok(())
+    }
+
+    /// First run of free sectors large enough, or the end of the file
+    fn allocate(&mut self, sectors: u32) -> u32 {
+        let sectors = sectors as usize;
+        let mut run_start = HEADER_SECTORS as usize;
+        let mut run_length = 0;
+        for sector in HEADER_SECTORS as usize..self.used.len() {
+            if self.used[sector] {
+                run_start = sector + 1;
+                run_length = 0;
+                continue;
+            }
+
+            run_length += 1;
+            if run_length == sectors {
+                break;
+            }
+        }
+
+        // Without a large enough gap, the trailing free sectors are extended past the end
+        if run_start + sectors > self.used.len() {
+            self.used.resize(run_start + sectors, false);
+        }
+        self.used[run_start..run_start + sectors].fill(true);
+        run_start as u32
+    }
+
+    fn free(&mut self, entry: Entry) {
+        if entry.is_empty() {
+            return;
+        }
+
+        let range = entry.sector as usize..(entry.sector + entry.sectors()) as usize;
+        self.used[range].fill(false);
+    }
+
+    fn stored(&self) -> impl Iterator<Item = usize> + '_ {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_idx, entry)| !entry.is_empty())
+            .map(|(idx, _entry)| idx)
+    }
+
+    fn needs_compaction(&self) -> bool {
+        let data_sectors = self.used.len() - HEADER_SECTORS as usize;
+        if data_sectors == 0 {
+            return false;
+        }
+
+        let free = self.used[HEADER_SECTORS as usize..]
+            .iter()
+            .filter(|used| !**used)
+            .count();
+        free as f32 / data_sectors as f32 > COMPACTION_THRESHOLD
+    }
+
+    /// Rewrite the region with its chunks packed one after another, dropping unused sectors
+    fn compact(&mut self) -> io::Result<()> {
+        let _span = info_span!("Compacting region file").entered();
+
+        let temp_path = self.path.with_extension("compact");
+        {
+            let mut compacted = File::create(&temp_path)?;
+            compacted.set_len(HEADER_SECTORS as u64 * SECTOR_SIZE)?;
+
+            let mut next_sector = HEADER_SECTORS;
+            for idx in self.stored().collect::<Vec<_>>() {
+                let Some(blob) = self.read(idx)? else {
+                    continue;
+                };
+                let entry = Entry {
+                    sector: next_sector,
+                    length: blob.len() as u32,
+                };
+
+                compacted.seek(SeekFrom::Start(entry.sector as u64 * SECTOR_SIZE))?;
+                compacted.write_all(&blob)?;
+                compacted.seek(SeekFrom::Start(idx as u64 * ENTRY_SIZE))?;
+                compacted.write_all(&entry.sector.to_le_bytes())?;
+                compacted.write_all(&entry.length.to_le_bytes())?;
+
+                next_sector += entry.sectors();
+            }
+            compacted.sync_all()?;
+        }
+
+        fs::rename(&temp_path, &self.path)?;
+        *self = Self::open(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Chunks grouped into region files, 32x32x32 chunks per file
+pub struct RegionChunkStore {
+    directory: PathBuf,
+    regions: Mutex<HashMap<IVec3, Arc<Mutex<RegionFile>>>>,
+}
+
+impl RegionChunkStore {
+    pub fn new(directory: impl Into<PathBuf>) -> io::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+
+        Ok(Self {
+            directory,
+            regions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Region holding a chunk and the index of the chunk inside it
+    fn locate(pos: ChunkPos) -> (IVec3, usize) {
+        let region = IVec3::new(
+            pos.x.div_euclid(REGION_EDGE),
+            pos.y.div_euclid(REGION_EDGE),
+            pos.z.div_euclid(REGION_EDGE),
+        );
+        let local = IVec3::new(
+            pos.x.rem_euclid(REGION_EDGE),
+            pos.y.rem_euclid(REGION_EDGE),
+            pos.z.rem_euclid(REGION_EDGE),
+        );
+        let idx = local.x + REGION_EDGE * (local.y + REGION_EDGE * local.z);
+        (region, idx as usize)
+    }
+
+    fn chunk_pos(region: IVec3, idx: usize) -> ChunkPos {
+        let idx = idx as i32;
+        ChunkPos::new(
+            region.x * REGION_EDGE + idx % REGION_EDGE,
+            region.y * REGION_EDGE + (idx / REGION_EDGE) % REGION_EDGE,
+            region.z * REGION_EDGE + idx / (REGION_EDGE * REGION_EDGE),
+        )
+    }
+
+    fn region_path(&self, region: IVec3) -> PathBuf {
+        self.directory.join(format!(
+            "r.{}.{}.{}.{}",
+            region.x, region.y, region.z, REGION_EXTENSION
+        ))
+    }
+
+    fn region(&self, region: IVec3) -> io::Result<Arc<Mutex<RegionFile>>> {
+        let mut regions = self.regions.lock().unwrap();
+        if let Some(file) = regions.get(&region) {
+            return Ok(file.clone());
+        }
+
+        let file = Arc::new(Mutex::new(RegionFile::open(&self.region_path(region))?));
+        regions.insert(region, file.clone());
+        Ok(file)
+    }
+
+    /// Regions with a file on disk
+    fn regions_on_disk(&self) -> io::Result<Vec<IVec3>> {
+        let mut regions = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(REGION_EXTENSION) {
+                continue;
+            }
+            let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let coords: Vec<i32> = stem
+                .split('.')
+                .skip(1)
+                .filter_map(|coord| coord.parse().ok())
+                .collect();
+            if let [x, y, z] = coords[..] {
+                regions.push(IVec3::new(x, y, z));
+            }
+        }
+        Ok(regions)
+    }
+}
+
+impl ChunkStore for RegionChunkStore {
+    fn load_blobs(&self, positions: &[ChunkPos]) -> Vec<(ChunkPos, io::Result<Option<Vec<u8>>>)> {
+        let _span = info_span!("Loading chunks from disk").entered();
+
+        positions
+            .iter()
+            .map(|pos| {
+                let (region, idx) = Self::locate(*pos);
+                let blob = self
+                    .region(region)
+                    .and_then(|file| file.lock().unwrap().read(idx));
+                (*pos, blob)
+            })
+            .collect()
+    }
+
+    fn save_blobs(&self, blobs: Vec<(ChunkPos, Vec<u8>)>) -> io::Result<()> {
+        let _span = info_span!("Saving chunks to disk").entered();
+
+        let mut by_region: HashMap<IVec3, Vec<(usize, Vec<u8>)>> = HashMap::new();
+        for (pos, blob) in blobs.into_iter() {
+            let (region, idx) = Self::locate(pos);
+            by_region.entry(region).or_default().push((idx, blob));
+        }
+
+        for (region, blobs) in by_region.into_iter() {
+            let file = self.region(region)?;
+            let mut file = file.lock().unwrap();
+            for (idx, blob) in blobs.iter() {
+                file.write(*idx, blob)?;
+            }
+            file.sync()?;
+
+            if file.needs_compaction() {
+                info!("Compacting region {}", region);
+                file.compact()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn quarantine(&self, pos: ChunkPos, reason: &str) -> io::Result<()> {
+        let (region, idx) = Self::locate(pos);
+        let file = self.region(region)?;
+        let mut file = file.lock().unwrap();
+        let Some(blob) = file.read(idx)? else {
+            return Ok(());
+        };
+
+        let quarantine = self.directory.join("quarantine");
+        fs::create_dir_all(&quarantine)?;
+        let name = format!("c.{}.{}.{}", pos.x, pos.y, pos.z);
+        fs::write(quarantine.join(format!("{name}.bin")), blob)?;
+        fs::write(quarantine.join(format!("{name}.txt")), reason)?;
+
+        file.remove(idx)?;
+        file.sync()
+    }
+
+    fn positions(&self) -> io::Result<Vec<ChunkPos>> {
+        let mut positions = Vec::new();
+        for region in self.regions_on_disk()? {
+            let file = self.region(region)?;
+            let file = file.lock().unwrap();
+            positions.extend(file.stored().map(|idx| Self::chunk_pos(region, idx)));
+        }
+        Ok(positions)
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        let mut regions = self.regions.lock().unwrap();
+        regions.clear();
+        for region in self.regions_on_disk()? {
+            fs::remove_file(self.region_path(region))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::io;
+
+use bevy::prelude::info_span;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, OptionalExtension};
+
+use super::ChunkStore;
+use crate::chunk::ChunkPos;
+
+/// One row per chunk in the `blocks` table of the world database
+pub struct SqliteChunkStore {
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl SqliteChunkStore {
+    pub fn new(pool: Pool<SqliteConnectionManager>) -> Self {
+        Self { pool }
+    }
+
+    fn connection(&self) -> io::Result<r2d2::PooledConnection<SqliteConnectionManager>> {
+        self.pool.get().map_err(io::Error::other)
+    }
+}
+
+impl ChunkStore for SqliteChunkStore {
+    fn load_blobs(&self, positions: &[ChunkPos]) -> Vec<(ChunkPos, io::Result<Option<Vec<u8>>>)> {
+        let _span = info_span!("Loading chunks from disk").entered();
+
+        let connection = match self.connection() {
+            Ok(connection) => connection,
+            Err(err) => {
+                return positions
+                    .iter()
+                    .map(|pos| (*pos, Err(io::Error::other(err.to_string()))))
+                    .collect();
+            }
+        };
+
+        positions
+            .iter()
+            .map(|pos| {
+                let blob = connection
+                    .prepare_cached(
+                        "SELECT data FROM blocks WHERE posx=:posx AND posy=:posy AND posz=:posz;",
+                    )
+                    .and_then(|mut stmt| {
+                        stmt.query_row(
+                            &[(":posx", &pos.x), (":posy", &pos.y), (":posz", &pos.z)],
+                            |row| row.get(0),
+                        )
+                        .optional()
+                    })
+                    .map_err(io::Error::other);
+                (*pos, blob)
+            })
+            .collect()
+    }
+
+    fn save_blobs(&self, blobs: Vec<(ChunkPos, Vec<u8>)>) -> io::Result<()> {
+        let _span = info_span!("Saving chunks to disk").entered();
+
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction().map_err(io::Error::other)?;
+        for (pos, blob) in blobs.iter() {
+            transaction
+                .execute(
+                    "REPLACE INTO blocks (posx, posy, posz, data) values (?1, ?2, ?3, ?4)",
+                    params![pos.x, pos.y, pos.z, blob],
+                )
+                .map_err(io::Error::other)?;
+        }
+        transaction.commit().map_err(io::Error::other)
+    }
+
+    fn quarantine(&self, pos: ChunkPos, reason: &str) -> io::Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction().map_err(io::Error::other)?;
+        transaction
+            .execute(
+                "INSERT INTO quarantined_blocks (posx, posy, posz, data, reason)
+                    SELECT posx, posy, posz, data, ?4 FROM blocks WHERE posx=?1 AND posy=?2 AND posz=?3",
+                params![pos.x, pos.y, pos.z, reason],
+            )
+            .map_err(io::Error::other)?;
+        transaction
+            .execute(
+                "DELETE FROM blocks WHERE posx=?1 AND posy=?2 AND posz=?3",
+                params![pos.x, pos.y, pos.z],
+            )
+            .map_err(io::Error::other)?;
+        transaction.commit().map_err(io::Error::other)
+    }
+
+    fn positions(&self) -> io::Result<Vec<ChunkPos>> {
+        let connection = self.connection()?;
+        let mut stmt = connection
+            .prepare("SELECT posx, posy, posz FROM blocks;")
+            .map_err(io::Error::other)?;
+        let positions = stmt
+            .query_map([], |row| {
+                Ok(ChunkPos::new(row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .map_err(io::Error::other)?
+            .collect::<Result<_, _>>()
+            .map_err(io::Error::other);
+        positions
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        self.connection()?
+            .execute("DELETE FROM blocks;", [])
+            .map(|_| ())
+            .map_err(io::Error::other)
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::Connection;
+
+use super::{
+    benchmark::CodecReport, database, format::ChunkCodec, loaded::Database, meta::WorldMeta,
+    store::ChunkStoreKind,
+};
+
+const WORLD_EXTENSION: &str = "db3";
+const REGIONS_EXTENSION: &str = "regions";
+/// Files SQLite may keep next to a world while it is open
+const SIDECAR_SUFFIXES: [&str; 2] = ["-wal", "-shm"];
+
+/// A single world on disk, identified by its name inside a saves directory
+#[derive(Clone, Debug)]
+pub struct WorldSave {
+    name: String,
+    path: PathBuf,
+}
+
+impl WorldSave {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+
+    /// Directory holding the region files, for worlds using the region chunk store
+    pub fn regions_path(&self) -> PathBuf {
+        self.path.with_extension(REGIONS_EXTENSION)
+    }
+
+    fn sidecars(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        SIDECAR_SUFFIXES.iter().map(|suffix| {
+            let mut path = self.path.clone().into_os_string();
+            path.push(suffix);
+            PathBuf::from(path)
+        })
+    }
+}
+
+/// Manages the worlds stored in a saves directory, one database file per world
+#[derive(Clone, Debug)]
+pub struct Worlds {
+    saves_dir: PathBuf,
+}
+
+impl Worlds {
+    pub fn new(saves_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            saves_dir: saves_dir.into(),
+        }
+    }
+
+    pub fn saves_dir(&self) -> &Path {
+        &self.saves_dir
+    }
+
+    /// Location of the named world, whether it exists or not
+    pub fn world(&self, name: &str) -> io::Result<WorldSave> {
+        if name.is_empty()
+            || name.starts_with('.')
+            || name.contains(|c: char| std::path::is_separator(c) || c.is_control())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid world name {name:?}"),
+            ));
+        }
+
+        Ok(WorldSave {
+            name: name.to_string(),
+            path: self.saves_dir.join(format!("{name}.{WORLD_EXTENSION}")),
+        })
+    }
+
+    /// Every world in the saves directory along with its metadata, sorted by name.
+    /// Files are only read, unreadable ones are listed without metadata.
+    pub fn list(&self) -> io::Result<Vec<(WorldSave, Option<WorldMeta>)>> {
+        if !self.saves_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut worlds = Vec::new();
+        for entry in fs::read_dir(&self.saves_dir)? {
+            let path = entry?.path();
+            if !path.is_file()
+                || path.extension().and_then(|ext| ext.to_str()) != Some(WORLD_EXTENSION)
+            {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            let Ok(world) = self.world(name) else {
+                continue;
+            };
+            let meta = database::read_world_meta(world.path());
+            worlds.push((world, meta));
+        }
+        worlds.sort_unstable_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+
+        Ok(worlds)
+    }
+
+    /// Create a new world with the given metadata, failing if the name is taken
+    pub fn create(&self, name: &str, meta: &WorldMeta) -> io::Result<WorldSave> {
+        let world = self.world(name)?;
+        if world.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("World {name:?} already exists"),
+            ));
+        }
+
+        fs::create_dir_all(&self.saves_dir)?;
+        Database::open(&world).save_meta(meta);
+
+        Ok(world)
+    }
+
+    /// Find an existing world
+    pub fn open(&self, name: &str) -> io::Result<WorldSave> {
+        let world = self.world(name)?;
+        if !world.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("World {name:?} does not exist"),
+            ));
+        }
+
+        Ok(world)
+    }
+
+    /// Find an existing world, or prepare a new one which gets a random seed when first opened
+    pub fn open_or_create(&self, name: &str) -> io::Result<WorldSave> {
+        let world = self.world(name)?;
+        fs::create_dir_all(&self.saves_dir)?;
+
+        Ok(world)
+    }
+
+    pub fn rename(&self, from: &str, to: &str) -> io::Result<WorldSave> {
+        let source = self.open(from)?;
+        let target = self.world(to)?;
+        if target.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("World {to:?} already exists"),
+            ));
+        }
+
+        fs::rename(&source.path, &target.path)?;
+        for (source_sidecar, target_sidecar) in source.sidecars().zip(target.sidecars()) {
+            if source_sidecar.exists() {
+                fs::rename(source_sidecar, target_sidecar)?;
+            }
+        }
+        if source.regions_path().is_dir() {
+            fs::rename(source.regions_path(), target.regions_path())?;
+        }
+
+        Ok(target)
+    }
+
+    /// Copy a world, including changes still sitting in its write-ahead log and its region files
+    pub fn copy(&self, from: &str, to: &str) -> io::Result<WorldSave> {
+        let source = self.open(from)?;
+        let target = self.world(to)?;
+        if target.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("World {to:?} already exists"),
+            ));
+        }
+
+        let connection = Connection::open(&source.path).map_err(io::Error::other)?;
+        connection
+            .execute(
+                "VACUUM INTO ?1",
+                [target.path.to_string_lossy().into_owned()],
+            )
+            .map_err(io::Error::other)?;
+
+        if source.regions_path().is_dir() {
+            copy_dir(&source.regions_path(), &target.regions_path())?;
+        }
+
+        Ok(target)
+    }
+
+    pub fn delete(&self, name: &str) -> io::Result<()> {
+        let world = self.open(name)?;
+
+        fs::remove_file(&world.path)?;
+        for sidecar in world.sidecars() {
+            if sidecar.exists() {
+                fs::remove_file(sidecar)?;
+            }
+        }
+        if world.regions_path().is_dir() {
+            fs::remove_dir_all(world.regions_path())?;
+        }
+
+        Ok(())
+    }
+
+    /// Move the chunks of a world to another storage backend
+    pub fn convert_chunk_store(&self, name: &str, to: ChunkStoreKind) -> io::Result<()> {
+        let world = self.open(name)?;
+        Database::open(&world).convert_chunk_store(to)
+    }
+
+    /// Rewrite every chunk of a world in the latest format, returns how many were upgraded
+    pub fn upgrade(&self, name: &str) -> io::Result<usize> {
+        let world = self.open(name)?;
+        Database::open(&world).upgrade_chunks()
+    }
+
+    /// Switch the chunk codec of a world, returns how many chunks were rewritten
+    pub fn set_compression(&self, name: &str, codec: ChunkCodec, level: i32) -> io::Result<usize> {
+        let world = self.open(name)?;
+        Database::open(&world).set_compression(codec, level)
+    }
+
+    /// Compare every chunk codec on a sample of the chunks of a world
+    pub fn benchmark_compression(
+        &self,
+        name: &str,
+        sample_size: usize,
+    ) -> io::Result<Vec<CodecReport>> {
+        let world = self.open(name)?;
+        Database::open(&world).benchmark_compression(sample_size)
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
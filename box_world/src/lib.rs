@@ -21,10 +21,12 @@ mod states;
 mod voxel;
 mod world_generator;
 
+pub use chunk::{BlockEntity, ChunkCodec, ChunkStoreKind, CodecReport, Persist, WorldSave, Worlds};
+
 const HORIZONTAL_VIEW_DISTANCE: u32 = 32;
 const VERTICAL_VIEW_DISTANCE: u32 = 12;
 
-pub fn app() -> App {
+pub fn app(world: WorldSave) -> App {
     let mut app = App::new();
 
     app.add_plugins(
@@ -72,7 +74,7 @@ pub fn app() -> App {
 
     app.add_plugins((
         voxel::VoxelPlugin,
-        chunk::ChunkPlugin,
+        chunk::ChunkPlugin::new(world),
         world_generator::GeneratorPlugin,
         mesher::MesherPlugin,
         player::PlayerPlugin,
@@ -96,6 +98,7 @@ fn setup(mut commands: Commands) {
         LoadPoint {
             horizontal: 8,
             vertical: 4,
+            ..default()
         },
         Name::new("Spawn"),
     ));
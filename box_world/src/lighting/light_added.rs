@@ -2,15 +2,20 @@ use std::collections::VecDeque;
 
 use bevy::{prelude::*, utils::HashSet};
 
-use crate::chunk::{ChunkData, ChunkPos, LoadedChunks};
+use crate::{
+    chunk::{ChunkData, ChunkPos, LoadedChunks},
+    voxel::VoxelRegistry,
+};
 
-use super::LightAddNode;
+use super::{LightAddNode, LightChannel};
 
 pub(super) fn handle_added(
     added_queue: &mut VecDeque<LightAddNode>,
     changed: &mut HashSet<ChunkPos>,
     chunks: &mut Query<(&ChunkPos, &mut ChunkData)>,
     loaded_chunks: &LoadedChunks,
+    voxel_registry: &VoxelRegistry,
+    channel: LightChannel,
 ) {
     while !added_queue.is_empty() {
         let node = added_queue.pop_front().unwrap();
@@ -20,9 +25,10 @@ pub(super) fn handle_added(
             let Ok((pos, chunk_data)) = chunks.get(node.chunk) else {
                 continue;
             };
-            (*pos, chunk_data.get_torchlight(x, y, z))
+            (*pos, channel.get(chunk_data, x, y, z))
         };
         let new_level = source_level.saturating_sub(1);
+        let down_level = channel.downward_level(source_level);
 
         changed.insert(pos);
 
@@ -35,35 +41,38 @@ pub(super) fn handle_added(
 
             check_neighbor_simple_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x - 1,
                 y,
                 z,
-                source_level,
                 new_level,
             );
 
             check_neighbor_simple_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x + 1,
                 y,
                 z,
-                source_level,
                 new_level,
             );
         } else if x == 0 {
             check_neighbor_complex_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 loaded_chunks,
                 chunks,
                 ChunkPos::new(pos.x - 1, pos.y, pos.z),
                 MAX,
                 y,
                 z,
-                source_level,
                 new_level,
             );
 
@@ -72,24 +81,26 @@ pub(super) fn handle_added(
             };
             check_neighbor_simple_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x + 1,
                 y,
                 z,
-                source_level,
                 new_level,
             );
         } else if x == MAX {
             check_neighbor_complex_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 loaded_chunks,
                 chunks,
                 ChunkPos::new(pos.x + 1, pos.y, pos.z),
                 0,
                 y,
                 z,
-                source_level,
                 new_level,
             );
 
@@ -98,12 +109,13 @@ pub(super) fn handle_added(
             };
             check_neighbor_simple_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x - 1,
                 y,
                 z,
-                source_level,
                 new_level,
             );
         }
@@ -115,36 +127,39 @@ pub(super) fn handle_added(
 
             check_neighbor_simple_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y - 1,
                 z,
-                source_level,
-                new_level,
+                down_level,
             );
 
             check_neighbor_simple_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y + 1,
                 z,
-                source_level,
                 new_level,
             );
         } else if y == 0 {
             check_neighbor_complex_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 loaded_chunks,
                 chunks,
                 ChunkPos::new(pos.x, pos.y - 1, pos.z),
                 x,
                 MAX,
                 z,
-                source_level,
-                new_level,
+                down_level,
             );
 
             let Ok((_pos, mut chunk_data)) = chunks.get_mut(node.chunk) else {
@@ -152,24 +167,26 @@ pub(super) fn handle_added(
             };
             check_neighbor_simple_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y + 1,
                 z,
-                source_level,
                 new_level,
             );
         } else if y == MAX {
             check_neighbor_complex_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 loaded_chunks,
                 chunks,
                 ChunkPos::new(pos.x, pos.y + 1, pos.z),
                 x,
                 0,
                 z,
-                source_level,
                 new_level,
             );
 
@@ -178,13 +195,14 @@ pub(super) fn handle_added(
             };
             check_neighbor_simple_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y - 1,
                 z,
-                source_level,
-                new_level,
+                down_level,
             );
         }
 
@@ -195,35 +213,38 @@ pub(super) fn handle_added(
 
             check_neighbor_simple_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y,
                 z - 1,
-                source_level,
                 new_level,
             );
 
             check_neighbor_simple_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y,
                 z + 1,
-                source_level,
                 new_level,
             );
         } else if z == 0 {
             check_neighbor_complex_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 loaded_chunks,
                 chunks,
                 ChunkPos::new(pos.x, pos.y, pos.z - 1),
                 x,
                 y,
                 MAX,
-                source_level,
                 new_level,
             );
 
@@ -232,24 +253,26 @@ pub(super) fn handle_added(
             };
             check_neighbor_simple_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y,
                 z + 1,
-                source_level,
                 new_level,
             );
         } else if z == MAX {
             check_neighbor_complex_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 loaded_chunks,
                 chunks,
                 ChunkPos::new(pos.x, pos.y, pos.z + 1),
                 x,
                 y,
                 0,
-                source_level,
                 new_level,
             );
 
@@ -258,12 +281,13 @@ pub(super) fn handle_added(
             };
             check_neighbor_simple_add(
                 added_queue,
+                voxel_registry,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y,
                 z - 1,
-                source_level,
                 new_level,
             );
         }
@@ -273,18 +297,19 @@ pub(super) fn handle_added(
 #[allow(clippy::too_many_arguments)]
 fn check_neighbor_simple_add(
     add_queue: &mut VecDeque<LightAddNode>,
+    voxel_registry: &VoxelRegistry,
+    channel: LightChannel,
     chunk_entity: Entity,
     chunk_data: &mut ChunkData,
     x: u32,
     y: u32,
     z: u32,
-    source_level: u8,
     new_level: u8,
 ) {
-    if !chunk_data.get(x, y, z).is_opaque()
-        && chunk_data.get_torchlight(x, y, z) + 2 <= source_level
+    if !voxel_registry.get_data(chunk_data.get(x, y, z)).is_opaque()
+        && channel.get(chunk_data, x, y, z) < new_level
     {
-        chunk_data.set_torchlight(x, y, z, new_level);
+        channel.set(chunk_data, x, y, z, new_level);
         add_queue.push_back(LightAddNode {
             idx: ChunkData::linearize(x, y, z),
             chunk: chunk_entity,
@@ -295,13 +320,14 @@ fn check_neighbor_simple_add(
 #[allow(clippy::too_many_arguments)]
 fn check_neighbor_complex_add(
     add_queue: &mut VecDeque<LightAddNode>,
+    voxel_registry: &VoxelRegistry,
+    channel: LightChannel,
     loaded_chunks: &LoadedChunks,
     chunks: &mut Query<(&ChunkPos, &mut ChunkData)>,
     pos: ChunkPos,
     x: u32,
     y: u32,
     z: u32,
-    source_level: u8,
     new_level: u8,
 ) {
     let (chunk_entity, mut chunk_data) = {
@@ -316,12 +342,13 @@ fn check_neighbor_complex_add(
 
     check_neighbor_simple_add(
         add_queue,
+        voxel_registry,
+        channel,
         *chunk_entity,
         &mut chunk_data,
         x,
         y,
         z,
-        source_level,
         new_level,
     );
 }
@@ -4,7 +4,7 @@ use bevy::{prelude::*, utils::HashSet};
 
 use crate::chunk::{ChunkData, ChunkPos, LoadedChunks};
 
-use super::{LightAddNode, LightRemNode};
+use super::{LightAddNode, LightChannel, LightRemNode};
 
 pub(super) fn handle_removed(
     add_queue: &mut VecDeque<LightAddNode>,
@@ -12,6 +12,7 @@ pub(super) fn handle_removed(
     changed: &mut HashSet<ChunkPos>,
     chunks: &mut Query<(&ChunkPos, &mut ChunkData)>,
     loaded_chunks: &LoadedChunks,
+    channel: LightChannel,
 ) {
     while !rem_queue.is_empty() {
         let node = rem_queue.pop_front().unwrap();
@@ -37,28 +38,33 @@ pub(super) fn handle_removed(
             check_neighbor_simple_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x - 1,
                 y,
                 z,
                 source_level,
+                false,
             );
 
             check_neighbor_simple_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x + 1,
                 y,
                 z,
                 source_level,
+                false,
             );
         } else if x == 0 {
             check_neighbor_complex_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 loaded_chunks,
                 chunks,
                 ChunkPos::new(pos.x - 1, pos.y, pos.z),
@@ -66,6 +72,7 @@ pub(super) fn handle_removed(
                 y,
                 z,
                 source_level,
+                false,
             );
 
             let Ok((_pos, mut chunk_data)) = chunks.get_mut(node.chunk) else {
@@ -74,17 +81,20 @@ pub(super) fn handle_removed(
             check_neighbor_simple_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x + 1,
                 y,
                 z,
                 source_level,
+                false,
             );
         } else if x == MAX {
             check_neighbor_complex_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 loaded_chunks,
                 chunks,
                 ChunkPos::new(pos.x + 1, pos.y, pos.z),
@@ -92,6 +102,7 @@ pub(super) fn handle_removed(
                 y,
                 z,
                 source_level,
+                false,
             );
 
             let Ok((_pos, mut chunk_data)) = chunks.get_mut(node.chunk) else {
@@ -100,12 +111,14 @@ pub(super) fn handle_removed(
             check_neighbor_simple_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x - 1,
                 y,
                 z,
                 source_level,
+                false,
             );
         }
 
@@ -117,28 +130,33 @@ pub(super) fn handle_removed(
             check_neighbor_simple_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y - 1,
                 z,
                 source_level,
+                true,
             );
 
             check_neighbor_simple_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y + 1,
                 z,
                 source_level,
+                false,
             );
         } else if y == 0 {
             check_neighbor_complex_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 loaded_chunks,
                 chunks,
                 ChunkPos::new(pos.x, pos.y - 1, pos.z),
@@ -146,6 +164,7 @@ pub(super) fn handle_removed(
                 MAX,
                 z,
                 source_level,
+                true,
             );
 
             let Ok((_pos, mut chunk_data)) = chunks.get_mut(node.chunk) else {
@@ -154,17 +173,20 @@ pub(super) fn handle_removed(
             check_neighbor_simple_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y + 1,
                 z,
                 source_level,
+                false,
             );
         } else if y == MAX {
             check_neighbor_complex_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 loaded_chunks,
                 chunks,
                 ChunkPos::new(pos.x, pos.y + 1, pos.z),
@@ -172,6 +194,7 @@ pub(super) fn handle_removed(
                 0,
                 z,
                 source_level,
+                false,
             );
 
             let Ok((_pos, mut chunk_data)) = chunks.get_mut(node.chunk) else {
@@ -180,12 +203,14 @@ pub(super) fn handle_removed(
             check_neighbor_simple_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y - 1,
                 z,
                 source_level,
+                true,
             );
         }
 
@@ -197,28 +222,33 @@ pub(super) fn handle_removed(
             check_neighbor_simple_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y,
                 z - 1,
                 source_level,
+                false,
             );
 
             check_neighbor_simple_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y,
                 z + 1,
                 source_level,
+                false,
             );
         } else if z == 0 {
             check_neighbor_complex_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 loaded_chunks,
                 chunks,
                 ChunkPos::new(pos.x, pos.y, pos.z - 1),
@@ -226,6 +256,7 @@ pub(super) fn handle_removed(
                 y,
                 MAX,
                 source_level,
+                false,
             );
 
             let Ok((_pos, mut chunk_data)) = chunks.get_mut(node.chunk) else {
@@ -234,17 +265,20 @@ pub(super) fn handle_removed(
             check_neighbor_simple_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y,
                 z + 1,
                 source_level,
+                false,
             );
         } else if z == MAX {
             check_neighbor_complex_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 loaded_chunks,
                 chunks,
                 ChunkPos::new(pos.x, pos.y, pos.z + 1),
@@ -252,6 +286,7 @@ pub(super) fn handle_removed(
                 y,
                 0,
                 source_level,
+                false,
             );
 
             let Ok((_pos, mut chunk_data)) = chunks.get_mut(node.chunk) else {
@@ -260,12 +295,14 @@ pub(super) fn handle_removed(
             check_neighbor_simple_rem(
                 add_queue,
                 rem_queue,
+                channel,
                 node.chunk,
                 &mut chunk_data,
                 x,
                 y,
                 z - 1,
                 source_level,
+                false,
             );
         }
     }
@@ -275,16 +312,21 @@ pub(super) fn handle_removed(
 fn check_neighbor_simple_rem(
     add_queue: &mut VecDeque<LightAddNode>,
     rem_queue: &mut VecDeque<LightRemNode>,
+    channel: LightChannel,
     chunk_entity: Entity,
     chunk_data: &mut ChunkData,
     x: u32,
     y: u32,
     z: u32,
     source_level: u8,
+    downward: bool,
 ) {
-    let curr_level = chunk_data.get_torchlight(x, y, z);
-    if curr_level != 0 && curr_level < source_level {
-        chunk_data.set_torchlight(x, y, z, 0);
+    let curr_level = channel.get(chunk_data, x, y, z);
+    // Light that doesn't fade going down was received from the source at the same level
+    let received_from_source = curr_level < source_level
+        || (downward && curr_level == channel.downward_level(source_level));
+    if curr_level != 0 && received_from_source {
+        channel.set(chunk_data, x, y, z, 0);
         rem_queue.push_back(LightRemNode {
             idx: ChunkData::linearize(x, y, z),
             chunk: chunk_entity,
@@ -302,6 +344,7 @@ fn check_neighbor_simple_rem(
 fn check_neighbor_complex_rem(
     add_queue: &mut VecDeque<LightAddNode>,
     rem_queue: &mut VecDeque<LightRemNode>,
+    channel: LightChannel,
     loaded_chunks: &LoadedChunks,
     chunks: &mut Query<(&ChunkPos, &mut ChunkData)>,
     pos: ChunkPos,
@@ -309,6 +352,7 @@ fn check_neighbor_complex_rem(
     y: u32,
     z: u32,
     source_level: u8,
+    downward: bool,
 ) {
     let (chunk_entity, mut chunk_data) = {
         let Some(chunk_entity) = loaded_chunks.get_chunk(pos) else {
@@ -323,11 +367,13 @@ fn check_neighbor_complex_rem(
     check_neighbor_simple_rem(
         add_queue,
         rem_queue,
+        channel,
         *chunk_entity,
         &mut chunk_data,
         x,
         y,
         z,
         source_level,
+        downward,
     );
 }
@@ -6,10 +6,14 @@ use crate::{
     chunk::{ChunkData, ChunkPos, LoadedChunks, VoxelAddedEvent, VoxelRemovedEvent},
     mesher::NeedsMesh,
     states::GameStates,
+    voxel::VoxelRegistry,
 };
 
-mod torch_added;
-mod torch_removed;
+mod light_added;
+mod light_removed;
+mod sunlight;
+
+const MAX_LIGHT: u8 = 15;
 
 pub struct LightingPlugin;
 
@@ -17,14 +21,49 @@ impl Plugin for LightingPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            propagate_lighting.run_if(in_state(GameStates::InGame)),
+            (
+                propagate_lighting.run_if(in_state(GameStates::InGame)),
+                sunlight::seed_sunlight.run_if(resource_exists::<VoxelRegistry>()),
+            ),
         );
     }
 }
 
+/// Chunk whose lighting has never been computed, such as a freshly generated chunk
 #[derive(Component, Clone, Copy, Debug)]
 pub struct NeedsLightPass;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LightChannel {
+    Torch,
+    Sun,
+}
+
+impl LightChannel {
+    fn get(&self, chunk_data: &ChunkData, x: u32, y: u32, z: u32) -> u8 {
+        match self {
+            LightChannel::Torch => chunk_data.get_torchlight(x, y, z),
+            LightChannel::Sun => chunk_data.get_sunlight(x, y, z),
+        }
+    }
+
+    fn set(&self, chunk_data: &mut ChunkData, x: u32, y: u32, z: u32, value: u8) {
+        match self {
+            LightChannel::Torch => chunk_data.set_torchlight(x, y, z, value),
+            LightChannel::Sun => chunk_data.set_sunlight(x, y, z, value),
+        }
+    }
+
+    /// Light level received by the voxel below a source of the given level
+    /// Full sunlight travels straight down without fading
+    fn downward_level(&self, source_level: u8) -> u8 {
+        match self {
+            LightChannel::Sun if source_level == MAX_LIGHT => MAX_LIGHT,
+            _ => source_level.saturating_sub(1),
+        }
+    }
+}
+
 struct LightAddNode {
     idx: usize,
     chunk: Entity,
@@ -40,11 +79,14 @@ pub fn propagate_lighting(
     mut commands: Commands,
     mut chunks: Query<(&ChunkPos, &mut ChunkData)>,
     loaded_chunks: Res<LoadedChunks>,
+    voxel_registry: Res<VoxelRegistry>,
     mut voxel_add_event: EventReader<VoxelAddedEvent>,
     mut voxel_rem_event: EventReader<VoxelRemovedEvent>,
 ) {
     let mut added_queue = VecDeque::new();
     let mut rem_queue = VecDeque::new();
+    let mut sun_added_queue = VecDeque::new();
+    let mut sun_rem_queue = VecDeque::new();
     let mut changed = HashSet::new();
 
     for event in voxel_rem_event.iter() {
@@ -64,6 +106,16 @@ pub fn propagate_lighting(
             val: source_level,
             chunk: *chunk_entity,
         });
+
+        // Opening a voxel lets neighboring sunlight flow back in
+        let source_level = chunk_data.get_sunlight(local_pos.x, local_pos.y, local_pos.z);
+        chunk_data.set_sunlight(local_pos.x, local_pos.y, local_pos.z, 0);
+
+        sun_rem_queue.push_back(LightRemNode {
+            idx: ChunkData::linearize(local_pos.x, local_pos.y, local_pos.z),
+            val: source_level,
+            chunk: *chunk_entity,
+        });
     }
 
     for event in voxel_add_event.iter() {
@@ -75,7 +127,8 @@ pub fn propagate_lighting(
             continue;
         };
 
-        if event.value.is_opaque() {
+        let voxel_data = voxel_registry.get_data(event.value);
+        if voxel_data.is_opaque() {
             let source_level = chunk_data.get_torchlight(local_pos.x, local_pos.y, local_pos.z);
             chunk_data.set_torchlight(local_pos.x, local_pos.y, local_pos.z, 0);
 
@@ -84,12 +137,22 @@ pub fn propagate_lighting(
                 val: source_level,
                 chunk: *chunk_entity,
             });
-        } else if event.value.emissiveness() > 0 {
+
+            // Blocking a voxel shadows the sunlight that went through it
+            let source_level = chunk_data.get_sunlight(local_pos.x, local_pos.y, local_pos.z);
+            chunk_data.set_sunlight(local_pos.x, local_pos.y, local_pos.z, 0);
+
+            sun_rem_queue.push_back(LightRemNode {
+                idx: ChunkData::linearize(local_pos.x, local_pos.y, local_pos.z),
+                val: source_level,
+                chunk: *chunk_entity,
+            });
+        } else if voxel_data.emissiveness() > 0 {
             chunk_data.set_torchlight(
                 local_pos.x,
                 local_pos.y,
                 local_pos.z,
-                event.value.emissiveness(),
+                voxel_data.emissiveness(),
             );
             added_queue.push_back(LightAddNode {
                 idx: ChunkData::linearize(local_pos.x, local_pos.y, local_pos.z),
@@ -98,14 +161,39 @@ pub fn propagate_lighting(
         }
     }
 
-    torch_removed::handle_removed(
+    light_removed::handle_removed(
         &mut added_queue,
         &mut rem_queue,
         &mut changed,
         &mut chunks,
         &loaded_chunks,
+        LightChannel::Torch,
+    );
+    light_added::handle_added(
+        &mut added_queue,
+        &mut changed,
+        &mut chunks,
+        &loaded_chunks,
+        &voxel_registry,
+        LightChannel::Torch,
+    );
+
+    light_removed::handle_removed(
+        &mut sun_added_queue,
+        &mut sun_rem_queue,
+        &mut changed,
+        &mut chunks,
+        &loaded_chunks,
+        LightChannel::Sun,
+    );
+    light_added::handle_added(
+        &mut sun_added_queue,
+        &mut changed,
+        &mut chunks,
+        &loaded_chunks,
+        &voxel_registry,
+        LightChannel::Sun,
     );
-    torch_added::handle_added(&mut added_queue, &mut changed, &mut chunks, &loaded_chunks);
 
     let changed: Vec<ChunkPos> = changed.into_iter().collect();
     for chunk_entity in loaded_chunks.get_unique_loaded_chunks_and_neighbors(&changed) {
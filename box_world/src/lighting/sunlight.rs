@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    chunk::{ChunkData, ChunkPos, LoadedChunks},
+    mesher::NeedsMesh,
+    voxel::VoxelRegistry,
+};
+
+use super::{
+    light_added, light_removed, LightAddNode, LightChannel, LightRemNode, NeedsLightPass, MAX_LIGHT,
+};
+
+const MAX_CHUNKS_PER_FRAME: usize = 64;
+const EDGE: usize = ChunkData::edge() as usize;
+
+/// Seed sunlight columns in chunks without lighting, then spread it with the usual BFS.
+/// Columns start from the bottom of the chunk above, or from the sky if nothing is loaded above.
+/// Chunks are lit top-down so a column always continues the light of the chunk above it.
+pub fn seed_sunlight(
+    mut commands: Commands,
+    mut chunks: Query<(&ChunkPos, &mut ChunkData)>,
+    needs_light: Query<(Entity, &ChunkPos), With<NeedsLightPass>>,
+    loaded_chunks: Res<LoadedChunks>,
+    voxel_registry: Res<VoxelRegistry>,
+) {
+    if needs_light.is_empty() {
+        return;
+    }
+
+    let pending: HashSet<ChunkPos> = needs_light.iter().map(|(_entity, pos)| *pos).collect();
+    let mut to_light: Vec<(Entity, ChunkPos)> = needs_light
+        .iter()
+        .map(|(entity, pos)| (entity, *pos))
+        .collect();
+    to_light.sort_unstable_by_key(|(_entity, pos)| -pos.y);
+
+    let mut lit = HashSet::new();
+    let mut added_queue = VecDeque::new();
+    let mut rem_queue = VecDeque::new();
+    let mut changed = HashSet::new();
+
+    const MAX: u32 = ChunkData::edge() - 1;
+
+    for (entity, pos) in to_light.into_iter() {
+        if lit.len() >= MAX_CHUNKS_PER_FRAME {
+            break;
+        }
+
+        let is_lit = |other: &ChunkPos| !pending.contains(other) || lit.contains(other);
+
+        // Columns receiving full sunlight from above, open sky if the chunk above isn't loaded
+        let above_pos = ChunkPos::new(pos.x, pos.y + 1, pos.z);
+        let mut sky = [[true; EDGE]; EDGE];
+        if let Some(above_entity) = loaded_chunks.get_chunk(above_pos) {
+            // Wait until the chunk above has data and lighting of its own
+            if !is_lit(&above_pos) {
+                continue;
+            }
+            let Ok((_pos, above_data)) = chunks.get(*above_entity) else {
+                continue;
+            };
+            for (z, row) in sky.iter_mut().enumerate() {
+                for (x, column) in row.iter_mut().enumerate() {
+                    *column = above_data.get_sunlight(x as u32, 0, z as u32) == MAX_LIGHT;
+                }
+            }
+        }
+
+        {
+            let Ok((_pos, mut chunk_data)) = chunks.get_mut(entity) else {
+                continue;
+            };
+
+            for (z, row) in sky.iter().enumerate() {
+                for (x, column) in row.iter().enumerate() {
+                    if !column {
+                        continue;
+                    }
+
+                    let (x, z) = (x as u32, z as u32);
+                    for y in (0..ChunkData::edge()).rev() {
+                        if voxel_registry.get_data(chunk_data.get(x, y, z)).is_opaque() {
+                            break;
+                        }
+                        chunk_data.set_sunlight(x, y, z, MAX_LIGHT);
+                    }
+                }
+            }
+
+            // Only lit voxels next to something darker need to spread
+            for idx in 0..ChunkData::usize() {
+                let (x, y, z) = ChunkData::delinearize(idx);
+                if chunk_data.get_sunlight(x, y, z) != MAX_LIGHT {
+                    continue;
+                }
+
+                let on_border = x == 0 || x == MAX || y == 0 || z == 0 || z == MAX;
+                if on_border
+                    || chunk_data.get_sunlight(x - 1, y, z) != MAX_LIGHT
+                    || chunk_data.get_sunlight(x + 1, y, z) != MAX_LIGHT
+                    || chunk_data.get_sunlight(x, y, z - 1) != MAX_LIGHT
+                    || chunk_data.get_sunlight(x, y, z + 1) != MAX_LIGHT
+                {
+                    added_queue.push_back(LightAddNode { idx, chunk: entity });
+                }
+            }
+        }
+
+        // Let light from already lit neighbors flow into this chunk
+        let faces = [
+            (
+                ChunkPos::new(pos.x - 1, pos.y, pos.z),
+                [Some(MAX), None, None],
+            ),
+            (
+                ChunkPos::new(pos.x + 1, pos.y, pos.z),
+                [Some(0), None, None],
+            ),
+            (
+                ChunkPos::new(pos.x, pos.y - 1, pos.z),
+                [None, Some(MAX), None],
+            ),
+            (
+                ChunkPos::new(pos.x, pos.y + 1, pos.z),
+                [None, Some(0), None],
+            ),
+            (
+                ChunkPos::new(pos.x, pos.y, pos.z - 1),
+                [None, None, Some(MAX)],
+            ),
+            (
+                ChunkPos::new(pos.x, pos.y, pos.z + 1),
+                [None, None, Some(0)],
+            ),
+        ];
+        for (neighbor_pos, fixed) in faces {
+            if !is_lit(&neighbor_pos) {
+                continue;
+            }
+            let Some(neighbor_entity) = loaded_chunks.get_chunk(neighbor_pos) else {
+                continue;
+            };
+            let Ok((_pos, neighbor_data)) = chunks.get(*neighbor_entity) else {
+                continue;
+            };
+
+            for a in 0..ChunkData::edge() {
+                for b in 0..ChunkData::edge() {
+                    let (x, y, z) = match fixed {
+                        [Some(x), _, _] => (x, a, b),
+                        [_, Some(y), _] => (a, y, b),
+                        [_, _, Some(z)] => (a, b, z),
+                        _ => unreachable!(),
+                    };
+                    if neighbor_data.get_sunlight(x, y, z) > 1 {
+                        added_queue.push_back(LightAddNode {
+                            idx: ChunkData::linearize(x, y, z),
+                            chunk: *neighbor_entity,
+                        });
+                    }
+                }
+            }
+        }
+
+        // The chunk below may have been lit assuming open sky before this chunk loaded
+        let below_pos = ChunkPos::new(pos.x, pos.y - 1, pos.z);
+        if is_lit(&below_pos) {
+            if let Some(below_entity) = loaded_chunks.get_chunk(below_pos) {
+                let mut shadowed = Vec::new();
+                if let Ok((_pos, chunk_data)) = chunks.get(entity) {
+                    for z in 0..ChunkData::edge() {
+                        for x in 0..ChunkData::edge() {
+                            if chunk_data.get_sunlight(x, 0, z) != MAX_LIGHT {
+                                shadowed.push((x, z));
+                            }
+                        }
+                    }
+                }
+
+                if let Ok((_pos, mut below_data)) = chunks.get_mut(*below_entity) {
+                    for (x, z) in shadowed {
+                        if below_data.get_sunlight(x, MAX, z) == MAX_LIGHT {
+                            below_data.set_sunlight(x, MAX, z, 0);
+                            rem_queue.push_back(LightRemNode {
+                                idx: ChunkData::linearize(x, MAX, z),
+                                val: MAX_LIGHT,
+                                chunk: *below_entity,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        lit.insert(pos);
+        changed.insert(pos);
+        commands.entity(entity).remove::<NeedsLightPass>();
+    }
+
+    light_removed::handle_removed(
+        &mut added_queue,
+        &mut rem_queue,
+        &mut changed,
+        &mut chunks,
+        &loaded_chunks,
+        LightChannel::Sun,
+    );
+    light_added::handle_added(
+        &mut added_queue,
+        &mut changed,
+        &mut chunks,
+        &loaded_chunks,
+        &voxel_registry,
+        LightChannel::Sun,
+    );
+
+    let changed: Vec<ChunkPos> = changed.into_iter().collect();
+    for chunk_entity in loaded_chunks.get_unique_loaded_chunks_and_neighbors(&changed) {
+        commands.entity(chunk_entity).insert(NeedsMesh);
+    }
+}
@@ -2,6 +2,12 @@ use std::io::Cursor;
 use winit::window::Icon;
 
 use bevy::{app::Startup, prelude::NonSend, winit::WinitWindows};
+use box_world::{ChunkCodec, ChunkStoreKind, Worlds};
+
+const SAVES_DIRECTORY: &str = "worlds";
+const DEFAULT_WORLD: &str = "world";
+/// Chunks measured by the compression benchmark
+const BENCHMARK_SAMPLES: usize = 1000;
 
 fn set_window_icon(winit_windows: NonSend<WinitWindows>) {
     let (icon_rgba, icon_width, icon_height) = {
@@ -22,7 +28,75 @@ fn set_window_icon(winit_windows: NonSend<WinitWindows>) {
 }
 
 fn main() {
-    let mut app = box_world::app();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let arg = |idx: usize| args.get(idx).map(String::as_str);
+    let world_name = |idx: usize| arg(idx).unwrap_or(DEFAULT_WORLD).to_string();
+    let worlds = Worlds::new(SAVES_DIRECTORY);
+
+    // Offline commands work on a world without starting the game
+    match arg(0) {
+        // `--upgrade <world>` rewrites a world in the latest format
+        Some("--upgrade") => {
+            let world_name = world_name(1);
+            let upgraded = worlds
+                .upgrade(&world_name)
+                .expect("Failed to upgrade world.");
+            println!("Upgraded {} chunks of world {}", upgraded, world_name);
+            return;
+        }
+        // `--compression <world> <none|zstd|zstd-dict> [level]` recompresses every chunk
+        Some("--compression") => {
+            let world_name = world_name(1);
+            let codec: ChunkCodec = arg(2)
+                .expect("Missing chunk codec.")
+                .parse()
+                .expect("Unknown chunk codec.");
+            let level = arg(3).map_or(0, |level| level.parse().expect("Invalid level."));
+            let count = worlds
+                .set_compression(&world_name, codec, level)
+                .expect("Failed to recompress world.");
+            println!(
+                "Compressed {} chunks of world {} with {}",
+                count, world_name, codec
+            );
+            return;
+        }
+        // `--chunk-store <world> <sqlite|region>` moves every chunk to another storage backend
+        Some("--chunk-store") => {
+            let world_name = world_name(1);
+            let store: ChunkStoreKind = arg(2)
+                .expect("Missing chunk store.")
+                .parse()
+                .expect("Unknown chunk store.");
+            worlds
+                .convert_chunk_store(&world_name, store)
+                .expect("Failed to convert world.");
+            println!("Moved the chunks of world {} to {}", world_name, store);
+            return;
+        }
+        // `--bench-compression <world> [samples]` compares codecs on chunks of the world
+        Some("--bench-compression") => {
+            let world_name = world_name(1);
+            let samples = arg(2).map_or(BENCHMARK_SAMPLES, |samples| {
+                samples.parse().expect("Invalid sample count.")
+            });
+            let reports = worlds
+                .benchmark_compression(&world_name, samples)
+                .expect("Failed to benchmark world.");
+            for report in reports {
+                println!("{}", report);
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    // The world to play can be picked by passing its name as the first argument
+    let world = worlds
+        .open_or_create(&world_name(0))
+        .expect("Failed to open world.");
+
+    let mut app = box_world::app(world);
 
     app.add_systems(Startup, set_window_icon);
     app.run();
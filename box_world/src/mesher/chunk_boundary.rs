@@ -5,9 +5,12 @@ use crate::{chunk::ChunkData, voxel::Voxel};
 const BOUNDARY_EDGE: usize = ChunkData::edge() as usize + 2;
 type BoundaryShape = ConstShape3usize<BOUNDARY_EDGE, BOUNDARY_EDGE, BOUNDARY_EDGE>;
 
+/// Voxels of a chunk surrounded by a one voxel layer of its neighbors
 pub struct ChunkBoundary {
-    voxels: Box<[Voxel; BoundaryShape::USIZE]>,
-    lights: Box<[u8; BoundaryShape::USIZE]>,
+    /// Voxels along each edge of the chunk, without the neighbor layer
+    edge: u32,
+    voxels: Vec<Voxel>,
+    lights: Vec<u8>,
 }
 
 #[allow(dead_code)]
@@ -19,9 +22,9 @@ impl ChunkBoundary {
         const MAX: u32 = ChunkData::edge();
         const BOUND: u32 = MAX + 1;
 
-        let voxels: Box<[Voxel; BoundaryShape::USIZE]> = (0..BoundaryShape::SIZE)
+        let voxels: Vec<Voxel> = (0..BoundaryShape::SIZE)
             .map(|idx| {
-                let (x, y, z) = ChunkBoundary::delinearize(idx);
+                let [x, y, z] = BoundaryShape::delinearize(idx).map(|coord| coord as u32);
                 match (x, y, z) {
                     (0, 0, 0) => neighbors[0].get(MAX - 1, MAX - 1, MAX - 1),
                     (0, 0, 1..=MAX) => neighbors[1].get(MAX - 1, MAX - 1, z - 1),
@@ -54,13 +57,11 @@ impl ChunkBoundary {
                     (_, _, _) => Voxel::default(),
                 }
             })
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap();
+            .collect();
 
-        let lights: Box<[u8; BoundaryShape::USIZE]> = (0..BoundaryShape::SIZE)
+        let lights: Vec<u8> = (0..BoundaryShape::SIZE)
             .map(|idx| {
-                let (x, y, z) = ChunkBoundary::delinearize(idx);
+                let [x, y, z] = BoundaryShape::delinearize(idx).map(|coord| coord as u32);
                 match (x, y, z) {
                     (0, 0, 0) => neighbors[0].get_light(MAX - 1, MAX - 1, MAX - 1),
                     (0, 0, 1..=MAX) => neighbors[1].get_light(MAX - 1, MAX - 1, z - 1),
@@ -93,47 +94,121 @@ impl ChunkBoundary {
                     (_, _, _) => 0,
                 }
             })
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap();
+            .collect();
 
-        Self { voxels, lights }
+        Self {
+            edge: MAX,
+            voxels,
+            lights,
+        }
     }
 
-    pub fn voxels(&self) -> &[Voxel; BoundaryShape::USIZE] {
-        &self.voxels
+    /// Coarser copy for distant chunks, each voxel standing for `scale`³ voxels of this one.
+    /// A voxel keeps the most common non-air voxel of its block when at least half of it is filled.
+    /// The neighbor layer is left empty so the chunk is closed on every side,
+    /// its border faces act as skirts over the cracks between levels of detail.
+    pub fn downsample(&self, scale: u32) -> Self {
+        let edge = self.edge / scale;
+        let boundary_edge = edge + 2;
+        let size = (boundary_edge * boundary_edge * boundary_edge) as usize;
+
+        // Voxels of this boundary covered by a coordinate of the downsampled one
+        let sources = |coord: u32| {
+            if coord == 0 {
+                0..=0
+            } else if coord == edge + 1 {
+                self.edge + 1..=self.edge + 1
+            } else {
+                (coord - 1) * scale + 1..=coord * scale
+            }
+        };
+
+        let mut voxels = vec![Voxel::default(); size];
+        let mut lights = vec![0; size];
+        let mut counts: Vec<(Voxel, u32)> = Vec::new();
+        for z in 0..boundary_edge {
+            for y in 0..boundary_edge {
+                for x in 0..boundary_edge {
+                    let inner = (1..=edge).contains(&x)
+                        && (1..=edge).contains(&y)
+                        && (1..=edge).contains(&z);
+
+                    counts.clear();
+                    let mut light = 0;
+                    let mut filled = 0;
+                    let mut total = 0;
+                    for source_z in sources(z) {
+                        for source_y in sources(y) {
+                            for source_x in sources(x) {
+                                let idx = self.linearize(source_x, source_y, source_z);
+                                light = light.max(self.lights[idx]);
+                                total += 1;
+
+                                let voxel = self.voxels[idx];
+                                if !inner || voxel.is_air() {
+                                    continue;
+                                }
+                                filled += 1;
+                                match counts.iter_mut().find(|(other, _count)| *other == voxel) {
+                                    Some((_voxel, count)) => *count += 1,
+                                    None => counts.push((voxel, 1)),
+                                }
+                            }
+                        }
+                    }
+
+                    let idx = (x + boundary_edge * (y + boundary_edge * z)) as usize;
+                    lights[idx] = light;
+                    if filled * 2 >= total {
+                        if let Some((voxel, _count)) =
+                            counts.iter().max_by_key(|(_voxel, count)| *count)
+                        {
+                            voxels[idx] = *voxel;
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            edge,
+            voxels,
+            lights,
+        }
     }
 
-    pub fn lights(&self) -> &[u8; BoundaryShape::USIZE] {
-        &self.lights
+    pub fn voxels(&self) -> &[Voxel] {
+        &self.voxels
     }
 
-    pub const fn edge() -> u32 {
-        BOUNDARY_EDGE as u32
+    pub fn lights(&self) -> &[u8] {
+        &self.lights
     }
 
-    pub const fn size() -> u32 {
-        BoundaryShape::SIZE as u32
+    /// Voxels along each edge of the chunk, without the neighbor layer
+    pub fn edge(&self) -> u32 {
+        self.edge
     }
 
-    pub fn linearize(x: u32, y: u32, z: u32) -> usize {
-        BoundaryShape::linearize([x as usize, y as usize, z as usize])
+    pub fn size(&self) -> u32 {
+        self.voxels.len() as u32
     }
 
-    pub fn delinearize(idx: usize) -> (u32, u32, u32) {
-        let res = BoundaryShape::delinearize(idx);
-        (res[0] as u32, res[1] as u32, res[2] as u32)
+    /// Same layout as the chunk shape, X first then Y then Z
+    pub fn linearize(&self, x: u32, y: u32, z: u32) -> usize {
+        let boundary_edge = self.edge + 2;
+        (x + boundary_edge * (y + boundary_edge * z)) as usize
     }
 
-    pub fn x_offset() -> usize {
-        ChunkBoundary::linearize(1, 0, 0) - ChunkBoundary::linearize(0, 0, 0)
+    pub fn x_offset(&self) -> usize {
+        self.linearize(1, 0, 0) - self.linearize(0, 0, 0)
     }
 
-    pub fn y_offset() -> usize {
-        ChunkBoundary::linearize(0, 1, 0) - ChunkBoundary::linearize(0, 0, 0)
+    pub fn y_offset(&self) -> usize {
+        self.linearize(0, 1, 0) - self.linearize(0, 0, 0)
     }
 
-    pub fn z_offset() -> usize {
-        ChunkBoundary::linearize(0, 0, 1) - ChunkBoundary::linearize(0, 0, 0)
+    pub fn z_offset(&self) -> usize {
+        self.linearize(0, 0, 1) - self.linearize(0, 0, 0)
     }
 }
@@ -1,6 +1,7 @@
-use std::ops::Deref;
-
-use crate::voxel::Voxel;
+use crate::{
+    chunk::{to_sunlight, to_torchlight},
+    voxel::{Voxel, VoxelRegistry},
+};
 
 use super::{
     chunk_boundary::ChunkBoundary,
@@ -23,219 +24,303 @@ impl<'a> Face<'a> {
         self.side
     }
 
+    /// Texture of the voxel for the side this face is on
     pub fn texture_indice(&self) -> u32 {
         self.quad.texture_indice
     }
 
+    /// Split the quad along the diagonal whose corners are brighter,
+    /// so ambient occlusion and light gradients look the same on every corner
     pub fn indices(&self, start: u32) -> [u32; 6] {
-        [start, start + 2, start + 1, start + 1, start + 2, start + 3]
+        let shades = self.shades();
+
+        if shades[1] + shades[2] > shades[0] + shades[3] {
+            [start, start + 2, start + 1, start + 1, start + 2, start + 3]
+        } else {
+            [start, start + 3, start + 1, start, start + 2, start + 3]
+        }
+    }
+
+    /// Brightness of each vertex from its ambient occlusion and light
+    fn shades(&self) -> [u32; 4] {
+        let aos = self.aos();
+        let lights = self.lights();
+        [0, 1, 2, 3].map(|vertex| {
+            let light = to_torchlight(lights[vertex]) as u32 + to_sunlight(lights[vertex]) as u32;
+            (aos[vertex] + 1) * (light + 1)
+        })
+    }
+
+    /// Size of the quad along each axis, in voxels
+    fn size(&self) -> [f32; 3] {
+        let (width, height) = (self.quad.width as f32, self.quad.height as f32);
+        match self.side.axis {
+            Axis::X => [1.0, height, width],
+            Axis::Y => [width, 1.0, height],
+            Axis::Z => [width, height, 1.0],
+        }
     }
 
     pub fn positions(&self, voxel_size: f32) -> [[f32; 3]; 4] {
-        let positions = match (&self.side.axis, &self.side.positive) {
-            (Axis::X, false) => [
-                [0.0, 0.0, 1.0],
-                [0.0, 0.0, 0.0],
-                [0.0, 1.0, 1.0],
-                [0.0, 1.0, 0.0],
-            ],
-            (Axis::X, true) => [
-                [1.0, 0.0, 0.0],
-                [1.0, 0.0, 1.0],
-                [1.0, 1.0, 0.0],
-                [1.0, 1.0, 1.0],
-            ],
-            (Axis::Y, false) => [
-                [0.0, 0.0, 1.0],
-                [1.0, 0.0, 1.0],
-                [0.0, 0.0, 0.0],
-                [1.0, 0.0, 0.0],
-            ],
-            (Axis::Y, true) => [
-                [0.0, 1.0, 1.0],
-                [0.0, 1.0, 0.0],
-                [1.0, 1.0, 1.0],
-                [1.0, 1.0, 0.0],
-            ],
-            (Axis::Z, false) => [
-                [0.0, 0.0, 0.0],
-                [1.0, 0.0, 0.0],
-                [0.0, 1.0, 0.0],
-                [1.0, 1.0, 0.0],
-            ],
-            (Axis::Z, true) => [
-                [1.0, 0.0, 1.0],
-                [0.0, 0.0, 1.0],
-                [1.0, 1.0, 1.0],
-                [0.0, 1.0, 1.0],
-            ],
-        };
+        let positions = unit_face(self.side);
 
         let (x, y, z) = (
             (self.quad.voxel[0] - 1) as f32,
             (self.quad.voxel[1] - 1) as f32,
             (self.quad.voxel[2] - 1) as f32,
         );
+        let [size_x, size_y, size_z] = self.size();
 
-        [
-            [
-                x * voxel_size + positions[0][0] * voxel_size,
-                y * voxel_size + positions[0][1] * voxel_size,
-                z * voxel_size + positions[0][2] * voxel_size,
-            ],
-            [
-                x * voxel_size + positions[1][0] * voxel_size,
-                y * voxel_size + positions[1][1] * voxel_size,
-                z * voxel_size + positions[1][2] * voxel_size,
-            ],
+        positions.map(|position| {
             [
-                x * voxel_size + positions[2][0] * voxel_size,
-                y * voxel_size + positions[2][1] * voxel_size,
-                z * voxel_size + positions[2][2] * voxel_size,
-            ],
-            [
-                x * voxel_size + positions[3][0] * voxel_size,
-                y * voxel_size + positions[3][1] * voxel_size,
-                z * voxel_size + positions[3][2] * voxel_size,
-            ],
-        ]
+                x * voxel_size + position[0] * size_x * voxel_size,
+                y * voxel_size + position[1] * size_y * voxel_size,
+                z * voxel_size + position[2] * size_z * voxel_size,
+            ]
+        })
     }
 
     pub fn normals(&self) -> [[f32; 3]; 4] {
         self.side.normals()
     }
 
+    /// Texture coordinates repeat once per voxel across the quad.
+    /// Rotated voxels ignore the flips and follow the block face shown instead.
     pub fn uvs(&self, flip_u: bool, flip_v: bool) -> [[f32; 2]; 4] {
-        match (flip_u, flip_v) {
+        if !self.quad.rotation.is_identity() {
+            return self.rotated_uvs();
+        }
+
+        let uvs = match (flip_u, flip_v) {
             (true, true) => [[1.0, 1.0], [0.0, 1.0], [1.0, 0.0], [0.0, 0.0]],
             (true, false) => [[1.0, 0.0], [0.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
             (false, true) => [[0.0, 1.0], [1.0, 1.0], [0.0, 0.0], [1.0, 0.0]],
             (false, false) => [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]],
-        }
+        };
+
+        // Top faces run the texture U along Z, every other face along the width
+        let (width, height) = (self.quad.width as f32, self.quad.height as f32);
+        let (u_scale, v_scale) = match (self.side.axis, self.side.positive) {
+            (Axis::Y, true) => (height, width),
+            _ => (width, height),
+        };
+
+        uvs.map(|[u, v]| [u * u_scale, v * v_scale])
+    }
+
+    /// Texture coordinates the block face shown on this side has in block space
+    fn rotated_uvs(&self) -> [[f32; 2]; 4] {
+        let rotation = self.quad.rotation;
+        let block_face = unit_face(Side::from(rotation.local_face(self.side.index())));
+        let size = self.size();
+
+        unit_face(self.side).map(|corner| {
+            let position = rotation
+                .inverse()
+                .rotate_point([0, 1, 2].map(|axis| corner[axis] * size[axis]));
+            // Distance from the first corner of the block face along each of its edges
+            let along = |edge_end: [f32; 3]| -> f32 {
+                (0..3)
+                    .map(|axis| {
+                        (position[axis] - block_face[0][axis])
+                            * (edge_end[axis] - block_face[0][axis])
+                    })
+                    .sum()
+            };
+            [along(block_face[1]), 1.0 - along(block_face[2])]
+        })
     }
 
     pub fn voxel(&self) -> [u32; 3] {
         self.quad.voxel
     }
-}
 
-pub struct FaceWithAO<'a> {
-    face: Face<'a>,
-    aos: [u32; 4],
-}
-
-impl<'a> Deref for FaceWithAO<'a> {
-    type Target = Face<'a>;
+    pub fn aos(&self) -> [u32; 4] {
+        self.quad.aos
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.face
+    /// Combined torch and sun light of each vertex
+    pub fn lights(&self) -> [u8; 4] {
+        self.quad.lights
     }
 }
 
-impl<'a> FaceWithAO<'a> {
-    pub fn new(face: Face<'a>, chunk: &ChunkBoundary) -> Self {
-        let aos = face_aos(&face, chunk);
-        Self { face, aos }
+/// Corners of the face of a unit voxel on a side, in the vertex order of every face
+pub(super) fn unit_face(side: Side) -> [[f32; 3]; 4] {
+    match (side.axis, side.positive) {
+        (Axis::X, false) => [
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 1.0],
+            [0.0, 1.0, 0.0],
+        ],
+        (Axis::X, true) => [
+            [1.0, 0.0, 0.0],
+            [1.0, 0.0, 1.0],
+            [1.0, 1.0, 0.0],
+            [1.0, 1.0, 1.0],
+        ],
+        (Axis::Y, false) => [
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 1.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+        ],
+        (Axis::Y, true) => [
+            [0.0, 1.0, 1.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 1.0, 1.0],
+            [1.0, 1.0, 0.0],
+        ],
+        (Axis::Z, false) => [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+        ],
+        (Axis::Z, true) => [
+            [1.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0],
+            [1.0, 1.0, 1.0],
+            [0.0, 1.0, 1.0],
+        ],
     }
+}
 
-    pub fn indices(&self, start: u32) -> [u32; 6] {
-        let aos = self.aos();
+pub(super) fn face_aos(
+    side: Side,
+    voxel: [u32; 3],
+    chunk: &ChunkBoundary,
+    voxel_registry: &VoxelRegistry,
+) -> [u32; 4] {
+    let voxels = chunk.voxels();
+    side_aos(
+        plane_neighbors(side, voxel, chunk).map(|idx| voxels[idx]),
+        voxel_registry,
+    )
+}
 
-        if aos[1] + aos[2] > aos[0] + aos[3] {
-            [start, start + 2, start + 1, start + 1, start + 2, start + 3]
-        } else {
-            [start, start + 3, start + 1, start, start + 2, start + 3]
-        }
-    }
+/// Light of each vertex, averaged over the voxel in front of the face
+/// and the non-opaque voxels around the vertex, with the same neighborhood as ambient occlusion
+pub(super) fn face_lights(
+    side: Side,
+    voxel: [u32; 3],
+    chunk: &ChunkBoundary,
+    voxel_registry: &VoxelRegistry,
+) -> [u8; 4] {
+    let voxels = chunk.voxels();
+    let lights = chunk.lights();
+    let front = lights[front_neighbor(side, voxel, chunk)];
+    let ns = plane_neighbors(side, voxel, chunk)
+        .map(|idx| (!voxel_registry.get_data(voxels[idx]).is_opaque()).then_some(lights[idx]));
 
-    pub fn aos(&self) -> [u32; 4] {
-        self.aos
+    [
+        vertex_light(front, ns[0], ns[1], ns[2]),
+        vertex_light(front, ns[2], ns[3], ns[4]),
+        vertex_light(front, ns[6], ns[7], ns[0]),
+        vertex_light(front, ns[4], ns[5], ns[6]),
+    ]
+}
+
+/// Index of the voxel the face looks into
+pub(super) fn front_neighbor(side: Side, voxel: [u32; 3], chunk: &ChunkBoundary) -> usize {
+    let [x, y, z] = voxel;
+    let idx = chunk.linearize(x, y, z);
+    let offset = match side.axis {
+        Axis::X => chunk.x_offset(),
+        Axis::Y => chunk.y_offset(),
+        Axis::Z => chunk.z_offset(),
+    };
+    if side.positive {
+        idx + offset
+    } else {
+        idx - offset
     }
 }
 
-fn face_aos(face: &Face, chunk: &ChunkBoundary) -> [u32; 4] {
-    let [x, y, z] = face.voxel();
-    let idx = ChunkBoundary::linearize(x, y, z);
+/// Indices of the 8 voxels around the one in front of the face, in the order `side_aos` expects
+fn plane_neighbors(side: Side, voxel: [u32; 3], chunk: &ChunkBoundary) -> [usize; 8] {
+    let [x, y, z] = voxel;
+    let idx = chunk.linearize(x, y, z);
 
-    let x_offset = ChunkBoundary::x_offset();
-    let y_offset = ChunkBoundary::y_offset();
-    let z_offset = ChunkBoundary::z_offset();
+    let x_offset = chunk.x_offset();
+    let y_offset = chunk.y_offset();
+    let z_offset = chunk.z_offset();
 
-    let voxels = chunk.voxels();
-    match face.side() {
-        Side::X_NEG => side_aos([
-            voxels[idx - x_offset + z_offset],
-            voxels[idx - x_offset - y_offset + z_offset],
-            voxels[idx - x_offset - y_offset],
-            voxels[idx - x_offset - y_offset - z_offset],
-            voxels[idx - x_offset - z_offset],
-            voxels[idx - x_offset + y_offset - z_offset],
-            voxels[idx - x_offset + y_offset],
-            voxels[idx - x_offset + y_offset + z_offset],
-        ]),
-        Side::X_POS => side_aos([
-            voxels[idx + x_offset - z_offset],
-            voxels[idx + x_offset - y_offset - z_offset],
-            voxels[idx + x_offset - y_offset],
-            voxels[idx + x_offset - y_offset + z_offset],
-            voxels[idx + x_offset + z_offset],
-            voxels[idx + x_offset + y_offset + z_offset],
-            voxels[idx + x_offset + y_offset],
-            voxels[idx + x_offset + y_offset - z_offset],
-        ]),
-        Side::Y_NEG => side_aos([
-            voxels[idx - x_offset - y_offset],
-            voxels[idx - x_offset - y_offset + z_offset],
-            voxels[idx - y_offset + z_offset],
-            voxels[idx + x_offset - y_offset + z_offset],
-            voxels[idx + x_offset - y_offset],
-            voxels[idx + x_offset - y_offset - z_offset],
-            voxels[idx - y_offset - z_offset],
-            voxels[idx - x_offset - y_offset - z_offset],
-        ]),
-        Side::Y_POS => side_aos([
-            voxels[idx + y_offset + z_offset],
-            voxels[idx - x_offset + y_offset + z_offset],
-            voxels[idx - x_offset + y_offset],
-            voxels[idx - x_offset + y_offset - z_offset],
-            voxels[idx + y_offset - z_offset],
-            voxels[idx + x_offset + y_offset - z_offset],
-            voxels[idx + x_offset + y_offset],
-            voxels[idx + x_offset + y_offset + z_offset],
-        ]),
-        Side::Z_NEG => side_aos([
-            voxels[idx - x_offset - z_offset],
-            voxels[idx - x_offset - y_offset - z_offset],
-            voxels[idx - y_offset - z_offset],
-            voxels[idx + x_offset - y_offset - z_offset],
-            voxels[idx + x_offset - z_offset],
-            voxels[idx + x_offset + y_offset - z_offset],
-            voxels[idx + y_offset - z_offset],
-            voxels[idx - x_offset + y_offset - z_offset],
-        ]),
-        Side::Z_POS => side_aos([
-            voxels[idx + x_offset + z_offset],
-            voxels[idx + x_offset - y_offset + z_offset],
-            voxels[idx - y_offset + z_offset],
-            voxels[idx - x_offset - y_offset + z_offset],
-            voxels[idx - x_offset + z_offset],
-            voxels[idx - x_offset + y_offset + z_offset],
-            voxels[idx + y_offset + z_offset],
-            voxels[idx + x_offset + y_offset + z_offset],
-        ]),
+    match side {
+        Side::X_NEG => [
+            idx - x_offset + z_offset,
+            idx - x_offset - y_offset + z_offset,
+            idx - x_offset - y_offset,
+            idx - x_offset - y_offset - z_offset,
+            idx - x_offset - z_offset,
+            idx - x_offset + y_offset - z_offset,
+            idx - x_offset + y_offset,
+            idx - x_offset + y_offset + z_offset,
+        ],
+        Side::X_POS => [
+            idx + x_offset - z_offset,
+            idx + x_offset - y_offset - z_offset,
+            idx + x_offset - y_offset,
+            idx + x_offset - y_offset + z_offset,
+            idx + x_offset + z_offset,
+            idx + x_offset + y_offset + z_offset,
+            idx + x_offset + y_offset,
+            idx + x_offset + y_offset - z_offset,
+        ],
+        Side::Y_NEG => [
+            idx - x_offset - y_offset,
+            idx - x_offset - y_offset + z_offset,
+            idx - y_offset + z_offset,
+            idx + x_offset - y_offset + z_offset,
+            idx + x_offset - y_offset,
+            idx + x_offset - y_offset - z_offset,
+            idx - y_offset - z_offset,
+            idx - x_offset - y_offset - z_offset,
+        ],
+        Side::Y_POS => [
+            idx + y_offset + z_offset,
+            idx - x_offset + y_offset + z_offset,
+            idx - x_offset + y_offset,
+            idx - x_offset + y_offset - z_offset,
+            idx + y_offset - z_offset,
+            idx + x_offset + y_offset - z_offset,
+            idx + x_offset + y_offset,
+            idx + x_offset + y_offset + z_offset,
+        ],
+        Side::Z_NEG => [
+            idx - x_offset - z_offset,
+            idx - x_offset - y_offset - z_offset,
+            idx - y_offset - z_offset,
+            idx + x_offset - y_offset - z_offset,
+            idx + x_offset - z_offset,
+            idx + x_offset + y_offset - z_offset,
+            idx + y_offset - z_offset,
+            idx - x_offset + y_offset - z_offset,
+        ],
+        Side::Z_POS => [
+            idx + x_offset + z_offset,
+            idx + x_offset - y_offset + z_offset,
+            idx - y_offset + z_offset,
+            idx - x_offset - y_offset + z_offset,
+            idx - x_offset + z_offset,
+            idx - x_offset + y_offset + z_offset,
+            idx + y_offset + z_offset,
+            idx + x_offset + y_offset + z_offset,
+        ],
     }
 }
 
-fn side_aos(neighbors: [Voxel; 8]) -> [u32; 4] {
+fn side_aos(neighbors: [Voxel; 8], voxel_registry: &VoxelRegistry) -> [u32; 4] {
     let ns = [
-        neighbors[0].is_opaque(),
-        neighbors[1].is_opaque(),
-        neighbors[2].is_opaque(),
-        neighbors[3].is_opaque(),
-        neighbors[4].is_opaque(),
-        neighbors[5].is_opaque(),
-        neighbors[6].is_opaque(),
-        neighbors[7].is_opaque(),
+        voxel_registry.get_data(neighbors[0]).is_opaque(),
+        voxel_registry.get_data(neighbors[1]).is_opaque(),
+        voxel_registry.get_data(neighbors[2]).is_opaque(),
+        voxel_registry.get_data(neighbors[3]).is_opaque(),
+        voxel_registry.get_data(neighbors[4]).is_opaque(),
+        voxel_registry.get_data(neighbors[5]).is_opaque(),
+        voxel_registry.get_data(neighbors[6]).is_opaque(),
+        voxel_registry.get_data(neighbors[7]).is_opaque(),
     ];
 
     [
@@ -255,3 +340,140 @@ fn ao_value(side1: bool, corner: bool, side2: bool) -> u32 {
         _ => 2,
     }
 }
+
+/// Torch and sun light are averaged separately.
+/// The corner only counts when one of the sides lets light through, like for ambient occlusion.
+fn vertex_light(front: u8, side1: Option<u8>, corner: Option<u8>, side2: Option<u8>) -> u8 {
+    let corner = corner.filter(|_| side1.is_some() || side2.is_some());
+    let samples = [Some(front), side1, corner, side2];
+
+    let (mut torchlight, mut sunlight, mut count) = (0, 0, 0);
+    for light in samples.into_iter().flatten() {
+        torchlight += to_torchlight(light) as u32;
+        sunlight += to_sunlight(light) as u32;
+        count += 1;
+    }
+    let average = |total: u32| ((total + count / 2) / count) as u8;
+    (average(sunlight) << 4) | average(torchlight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::ChunkData,
+        mesher::VoxelVisibility,
+        voxel::{Rotation, VoxelData},
+    };
+
+    /// Voxel of the chunk whose top face is tested
+    const VOXEL: [u32; 3] = [8, 8, 8];
+
+    fn registry() -> VoxelRegistry {
+        VoxelRegistry::from_data(vec![
+            ("air", VoxelData::default()),
+            ("stone", VoxelData::with_visibility(VoxelVisibility::Opaque)),
+        ])
+    }
+
+    /// Offsets along X and Z of the voxels around the one above `VOXEL`,
+    /// for the corners of the top face in vertex order
+    const CORNERS: [[i32; 2]; 4] = [[-1, 1], [-1, -1], [1, 1], [1, -1]];
+
+    /// Top face of `VOXEL`, with the voxels and lights of the chunk around it
+    fn top_face_indices(chunk: ChunkData, registry: &VoxelRegistry) -> (Quad, [u32; 6]) {
+        let boundary = ChunkBoundary::new(chunk, vec![ChunkData::default(); 26]);
+        let voxel = VOXEL.map(|coord| coord + 1);
+        let quad = Quad {
+            voxel,
+            texture_indice: 0,
+            rotation: Rotation::IDENTITY,
+            lights: face_lights(Side::Y_POS, voxel, &boundary, registry),
+            aos: face_aos(Side::Y_POS, voxel, &boundary, registry),
+            width: 1,
+            height: 1,
+        };
+        let indices = Face::new(Side::Y_POS, &quad).indices(0);
+        (quad, indices)
+    }
+
+    /// Vertices shared by both triangles of a quad
+    fn diagonal(indices: [u32; 6]) -> [u32; 2] {
+        let mut shared: Vec<u32> = indices[..3]
+            .iter()
+            .copied()
+            .filter(|vertex| indices[3..].contains(vertex))
+            .collect();
+        shared.sort_unstable();
+        shared.try_into().unwrap()
+    }
+
+    fn chunk_with_stone(registry: &VoxelRegistry) -> ChunkData {
+        let mut chunk = ChunkData::default();
+        let [x, y, z] = VOXEL;
+        chunk.set(x, y, z, registry.get_voxel("stone"));
+        chunk
+    }
+
+    #[test]
+    fn splits_along_the_brighter_diagonal() {
+        let registry = registry();
+        let [x, y, z] = VOXEL;
+
+        for pattern in 0..16 {
+            let occluded = |vertex: usize| pattern & (1 << vertex) != 0;
+            let mut chunk = chunk_with_stone(&registry);
+            for (vertex, [dx, dz]) in CORNERS.into_iter().enumerate() {
+                if occluded(vertex) {
+                    let (corner_x, corner_z) = (x as i32 + dx, z as i32 + dz);
+                    chunk.set(
+                        corner_x as u32,
+                        y + 1,
+                        corner_z as u32,
+                        registry.get_voxel("stone"),
+                    );
+                }
+            }
+
+            let (quad, indices) = top_face_indices(chunk, &registry);
+            let expected_aos = [0, 1, 2, 3].map(|vertex| if occluded(vertex) { 2 } else { 3 });
+            assert_eq!(quad.aos, expected_aos, "pattern {:04b}", pattern);
+
+            // Ties keep the default diagonal
+            let darkened =
+                |vertices: [usize; 2]| vertices.into_iter().filter(|v| occluded(*v)).count();
+            let expected = if darkened([0, 3]) > darkened([1, 2]) {
+                [1, 2]
+            } else {
+                [0, 3]
+            };
+            assert_eq!(diagonal(indices), expected, "pattern {:04b}", pattern);
+
+            // Both triangles together cover every vertex
+            let mut vertices = indices.to_vec();
+            vertices.sort_unstable();
+            vertices.dedup();
+            assert_eq!(vertices, [0, 1, 2, 3], "pattern {:04b}", pattern);
+        }
+    }
+
+    #[test]
+    fn splits_through_the_lit_corner() {
+        let registry = registry();
+        let [x, y, z] = VOXEL;
+
+        let (_quad, dark) = top_face_indices(chunk_with_stone(&registry), &registry);
+        assert_eq!(diagonal(dark), [0, 3]);
+
+        // Light coming from the X+ Z+ corner of the face, without any occlusion
+        let mut chunk = chunk_with_stone(&registry);
+        for (dx, dz) in [(1, 0), (1, 1), (0, 1)] {
+            chunk.set_torchlight(x + dx, y + 1, z + dz, 15);
+        }
+
+        let (quad, lit) = top_face_indices(chunk, &registry);
+        assert_eq!(quad.aos, [3; 4]);
+        assert_eq!(quad.lights.map(to_torchlight), [4, 0, 11, 4]);
+        assert_eq!(diagonal(lit), [1, 2]);
+    }
+}
@@ -1,23 +1,63 @@
 use bevy::{
-    prelude::{info_span, Mesh},
+    prelude::{info_span, Mesh, Vec3},
     render::{mesh::Indices, render_resource::PrimitiveTopology},
 };
+use bevy_rapier3d::prelude::Collider;
 
-use crate::chunk::{to_sunlight, to_torchlight};
+use crate::{
+    chunk::{to_sunlight, to_torchlight},
+    voxel::VoxelRegistry,
+};
 
 use super::{
     chunk_boundary::ChunkBoundary,
     quads::{generate_quads_with_buffer, QuadGroups},
+    shapes::{generate_shape_faces, ShapeFace},
 };
 
 //const UV_SCALE: f32 = 1.0 / 16.0;
 
-pub fn generate_mesh(chunk: ChunkBoundary) -> (Option<Mesh>, Option<Mesh>) {
+/// How a chunk is turned into a mesh
+#[derive(Clone, Copy)]
+pub struct MeshOptions {
+    /// World size of a voxel of the boundary, larger than one for downsampled chunks
+    pub voxel_size: f32,
+    pub smooth_lighting: bool,
+    /// Also build a collider matching the mesh, without the faces of shapes that can be walked through
+    pub colliders: bool,
+}
+
+/// Mesh of one pass of a chunk and its collider
+pub struct ChunkMesh {
+    pub mesh: Mesh,
+    pub collider: Option<Collider>,
+}
+
+pub fn generate_mesh(
+    chunk: ChunkBoundary,
+    options: MeshOptions,
+    voxel_registry: &VoxelRegistry,
+) -> (Option<ChunkMesh>, Option<ChunkMesh>) {
     let _span = info_span!("Generate mesh only").entered();
     let mut buffer = QuadGroups::default();
-
-    let solid_mesh = generate_mesh_with_buffer(true, &chunk, &mut buffer);
-    let transparent_mesh = generate_mesh_with_buffer(false, &chunk, &mut buffer);
+    let mut shape_faces = Vec::new();
+
+    let solid_mesh = generate_mesh_with_buffer(
+        true,
+        &chunk,
+        options,
+        voxel_registry,
+        &mut buffer,
+        &mut shape_faces,
+    );
+    let transparent_mesh = generate_mesh_with_buffer(
+        false,
+        &chunk,
+        options,
+        voxel_registry,
+        &mut buffer,
+        &mut shape_faces,
+    );
 
     (solid_mesh, transparent_mesh)
 }
@@ -27,11 +67,21 @@ pub fn generate_mesh(chunk: ChunkBoundary) -> (Option<Mesh>, Option<Mesh>) {
 pub fn generate_mesh_with_buffer(
     solid_pass: bool,
     chunk: &ChunkBoundary,
+    options: MeshOptions,
+    voxel_registry: &VoxelRegistry,
     buffer: &mut QuadGroups,
-) -> Option<Mesh> {
-    generate_quads_with_buffer(solid_pass, chunk, buffer);
-
-    let num_quads = buffer.num_quads();
+    shape_faces: &mut Vec<ShapeFace>,
+) -> Option<ChunkMesh> {
+    generate_quads_with_buffer(
+        solid_pass,
+        options.smooth_lighting,
+        chunk,
+        voxel_registry,
+        buffer,
+    );
+    generate_shape_faces(solid_pass, chunk, voxel_registry, shape_faces);
+
+    let num_quads = buffer.num_quads() + shape_faces.len();
     if num_quads == 0 {
         return None;
     }
@@ -46,36 +96,63 @@ pub fn generate_mesh_with_buffer(
     let mut lights = Vec::with_capacity(num_vertices);
     let mut ao = Vec::with_capacity(num_vertices);
     let mut texture_indices = Vec::with_capacity(num_vertices);
+    let mut collider_indices = Vec::new();
 
-    for face in buffer.iter_with_ao(chunk) {
+    for face in buffer.iter() {
         indices.extend_from_slice(&face.indices(positions.len() as u32));
-        positions.extend_from_slice(&face.positions(1.0));
+        positions.extend_from_slice(&face.positions(options.voxel_size));
         normals.extend_from_slice(&face.normals());
         ao.extend_from_slice(&face.aos());
         texture_indices.extend_from_slice(&[face.texture_indice(); 4]);
 
-        let [face_x, face_y, face_z] = face.voxel();
-        let (x, y, z) = match (face.side().axis, face.side().positive) {
-            (super::side::Axis::X, true) => (face_x + 1, face_y, face_z),
-            (super::side::Axis::X, false) => (face_x - 1, face_y, face_z),
-            (super::side::Axis::Y, true) => (face_x, face_y + 1, face_z),
-            (super::side::Axis::Y, false) => (face_x, face_y - 1, face_z),
-            (super::side::Axis::Z, true) => (face_x, face_y, face_z + 1),
-            (super::side::Axis::Z, false) => (face_x, face_y, face_z - 1),
-        };
-        let combined_light = chunk.lights()[ChunkBoundary::linearize(x, y, z)];
-        let torchlight = convert_light(to_torchlight(combined_light));
-        let sunlight = convert_light(to_sunlight(combined_light));
-        lights.extend_from_slice(&[
-            [torchlight, sunlight],
-            [torchlight, sunlight],
-            [torchlight, sunlight],
-            [torchlight, sunlight],
-        ]);
+        lights.extend_from_slice(&face.lights().map(|light| {
+            [
+                convert_light(to_torchlight(light)),
+                convert_sunlight(to_sunlight(light)),
+            ]
+        }));
 
         tex_coords.extend_from_slice(&face.uvs(false, true));
     }
 
+    // Every cube face collides, the shape faces are added after them
+    if options.colliders {
+        collider_indices.extend(indices.chunks_exact(3).map(|tri| [tri[0], tri[1], tri[2]]));
+    }
+
+    for face in shape_faces.iter() {
+        let start = positions.len() as u32;
+        let face_indices = [start, start + 3, start + 1, start, start + 2, start + 3];
+        indices.extend_from_slice(&face_indices);
+        if options.colliders && face.collides {
+            collider_indices.extend(
+                face_indices
+                    .chunks_exact(3)
+                    .map(|tri| [tri[0], tri[1], tri[2]]),
+            );
+        }
+
+        positions.extend(
+            face.positions
+                .map(|position| position.map(|coord| coord * options.voxel_size)),
+        );
+        normals.extend_from_slice(&[face.normal; 4]);
+        ao.extend_from_slice(&[3; 4]);
+        texture_indices.extend_from_slice(&[face.texture_indice; 4]);
+        lights.extend_from_slice(
+            &[[
+                convert_light(to_torchlight(face.light)),
+                convert_sunlight(to_sunlight(face.light)),
+            ]; 4],
+        );
+        tex_coords.extend_from_slice(&face.uvs);
+    }
+
+    let collider = (options.colliders && !collider_indices.is_empty()).then(|| {
+        let vertices = positions.iter().copied().map(Vec3::from).collect();
+        Collider::trimesh(vertices, collider_indices)
+    });
+
     let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
     mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
@@ -85,7 +162,7 @@ pub fn generate_mesh_with_buffer(
     mesh.insert_attribute(super::render::ATTRIBUTE_VOXEL_LIGHTS, lights);
     mesh.set_indices(Some(Indices::U32(indices)));
 
-    Some(mesh)
+    Some(ChunkMesh { mesh, collider })
 }
 
 fn convert_ao(ao: &[u32]) -> Vec<[f32; 4]> {
@@ -120,3 +197,9 @@ fn convert_light(light: u8) -> f32 {
         _ => 10.0,
     }
 }
+
+/// Sunlight only brightens on top of the torchlight floor, so caves are darker than open terrain
+fn convert_sunlight(light: u8) -> f32 {
+    let ratio = light.min(15) as f32 / 15.0;
+    ratio * ratio * 0.5
+}
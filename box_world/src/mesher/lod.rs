@@ -0,0 +1,50 @@
+use bevy::prelude::*;
+
+use crate::chunk::{ChunkPos, LoadRangesChangedEvent, LoadedChunks};
+
+use super::NeedsMesh;
+
+/// Distance in chunks to the nearest meshing load point where each coarser level starts
+const LOD_DISTANCES: [f32; 3] = [8.0, 16.0, 24.0];
+
+/// Level of detail of a chunk mesh, each level halves the resolution of the previous one
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MeshLod(pub u8);
+
+impl MeshLod {
+    pub fn at(world: &LoadedChunks, pos: ChunkPos) -> Self {
+        let Some(distance) = world.mesh_distance(pos) else {
+            return Self::default();
+        };
+        Self(
+            LOD_DISTANCES
+                .iter()
+                .take_while(|start| distance >= **start)
+                .count() as u8,
+        )
+    }
+
+    /// Full resolution voxels along each edge of a voxel at this level
+    pub fn scale(self) -> u32 {
+        1 << self.0
+    }
+}
+
+/// Mesh again the chunks whose level of detail changed after load points moved
+pub(super) fn update_mesh_lods(
+    mut commands: Commands,
+    mut ranges_changed: EventReader<LoadRangesChangedEvent>,
+    world: Res<LoadedChunks>,
+    meshed: Query<(Entity, &ChunkPos, &MeshLod), Without<NeedsMesh>>,
+) {
+    if ranges_changed.is_empty() {
+        return;
+    }
+    ranges_changed.clear();
+
+    for (entity, pos, lod) in meshed.iter() {
+        if MeshLod::at(&world, *pos) != *lod {
+            commands.entity(entity).insert(NeedsMesh);
+        }
+    }
+}
@@ -5,35 +5,57 @@ use bevy::{
     tasks::{AsyncComputeTaskPool, Task},
 };
 use bevy_asset_loader::prelude::*;
-use bevy_rapier3d::prelude::{AsyncCollider, Collider, ComputedColliderShape};
+use bevy_rapier3d::prelude::Collider;
 use futures_lite::future;
 
 use crate::{
-    chunk::{ChunkData, ChunkPos, LoadedChunks},
+    chunk::{ChunkData, ChunkPos, ChunkPriorities, LoadedChunks},
+    lighting::NeedsLightPass,
     states::GameStates,
+    voxel::VoxelRegistry,
 };
 
-use self::{chunk_boundary::ChunkBoundary, generate::generate_mesh, render::*};
+use self::{
+    chunk_boundary::ChunkBoundary,
+    generate::{generate_mesh, ChunkMesh, MeshOptions},
+    render::*,
+};
 
 mod chunk_boundary;
 mod face;
 mod generate;
+mod lod;
 mod quads;
 mod render;
+mod shapes;
 mod side;
 mod visibility;
 
+pub use lod::MeshLod;
 pub use visibility::VoxelVisibility;
 
 pub struct MesherPlugin;
 
 impl Plugin for MesherPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(Update, (enqueue_meshing_tasks, rapier_slowdown_workaround))
-            .add_systems(
-                PostUpdate,
-                handle_done_meshing_tasks.run_if(resource_exists::<TerrainMaterial>()),
-            );
+        app.add_systems(
+            Update,
+            (
+                lod::update_mesh_lods,
+                toggle_smooth_lighting,
+                enqueue_meshing_tasks
+                    .run_if(resource_exists::<VoxelRegistry>())
+                    .after(lod::update_mesh_lods)
+                    .after(toggle_smooth_lighting),
+                rapier_slowdown_workaround,
+            ),
+        )
+        .add_systems(
+            PostUpdate,
+            handle_done_meshing_tasks.run_if(resource_exists::<TerrainMaterial>()),
+        );
+
+        app.insert_resource(SmoothLighting(true));
 
         app.add_plugins(MaterialPlugin::<TerrainTextureMaterial>::default())
             .add_collection_to_loading_state::<_, render::TerrainTexture>(GameStates::AssetLoading)
@@ -45,9 +67,35 @@ impl Plugin for MesherPlugin {
 #[component(storage = "SparseSet")]
 pub struct NeedsMesh;
 
+/// Average light around each vertex instead of using the light in front of each face
+#[derive(Resource)]
+pub struct SmoothLighting(pub bool);
+
+/// Switch between smooth and flat lighting with F2, meshing every chunk again
+fn toggle_smooth_lighting(
+    mut commands: Commands,
+    mut smooth_lighting: ResMut<SmoothLighting>,
+    kb_input: Res<Input<KeyCode>>,
+    meshed: Query<Entity, With<MeshLod>>,
+) {
+    if !kb_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+
+    smooth_lighting.0 = !smooth_lighting.0;
+    info!(
+        "Smooth lighting {}",
+        if smooth_lighting.0 { "on" } else { "off" }
+    );
+    for entity in meshed.iter() {
+        commands.entity(entity).insert(NeedsMesh);
+    }
+}
+
 struct ComputedMesh {
-    solid_mesh: Option<Mesh>,
-    transparent_mesh: Option<Mesh>,
+    solid_mesh: Option<ChunkMesh>,
+    transparent_mesh: Option<ChunkMesh>,
+    lod: MeshLod,
 }
 
 #[derive(Component)]
@@ -56,14 +104,40 @@ struct ComputeMesh(Task<ComputedMesh>);
 fn enqueue_meshing_tasks(
     mut commands: Commands,
     world: Res<LoadedChunks>,
-    needs_mesh: Query<(Entity, &ChunkPos, &ChunkData), With<NeedsMesh>>,
+    voxel_registry: Res<VoxelRegistry>,
+    priorities: Res<ChunkPriorities>,
+    smooth_lighting: Res<SmoothLighting>,
+    needs_mesh: Query<(Entity, &ChunkPos, &ChunkData), (With<NeedsMesh>, Without<NeedsLightPass>)>,
     chunks: Query<&ChunkData>,
 ) {
     let thread_pool = AsyncComputeTaskPool::get();
 
-    needs_mesh.iter().take(512).for_each(|(entity, pos, data)| {
+    let most_urgent = priorities.most_urgent(needs_mesh.iter().map(|item| (*item.1, item)), 512);
+    most_urgent.into_iter().for_each(|(entity, pos, data)| {
         commands.entity(entity).remove::<NeedsMesh>();
 
+        // Drop the meshes of chunks only loaded for their data
+        let Some(policy) = world.policy_at(*pos).filter(|policy| policy.meshes()) else {
+            commands
+                .entity(entity)
+                .remove::<(
+                    MaterialMeshBundle<TerrainTextureMaterial>,
+                    Collider,
+                    Aabb,
+                    ComputeMesh,
+                    MeshLod,
+                )>()
+                .despawn_descendants();
+            return;
+        };
+        let lod = MeshLod::at(&world, *pos);
+        let options = MeshOptions {
+            voxel_size: lod.scale() as f32,
+            smooth_lighting: smooth_lighting.0,
+            // Only full resolution meshes get colliders, when the load points covering the chunk want them
+            colliders: policy.has_physics() && lod == MeshLod(0),
+        };
+
         // Skip meshing if chunk is empty, garanteed empty mesh
         if data.is_empty() {
             return;
@@ -89,13 +163,19 @@ fn enqueue_meshing_tasks(
         // Clone out of needs_meshes before moving into task
         let neighbors: Vec<ChunkData> = neighbors.into_iter().cloned().collect();
         let data = data.clone();
+        let voxel_registry = voxel_registry.clone();
 
         let task = thread_pool.spawn(async move {
             let _span = info_span!("Generate mesh and chunk boundary").entered();
-            let result = generate_mesh(ChunkBoundary::new(data, neighbors));
+            let mut boundary = ChunkBoundary::new(data, neighbors);
+            if lod.scale() > 1 {
+                boundary = boundary.downsample(lod.scale());
+            }
+            let result = generate_mesh(boundary, options, &voxel_registry);
             ComputedMesh {
                 solid_mesh: result.0,
                 transparent_mesh: result.1,
+                lod,
             }
         });
         commands.entity(entity).insert(ComputeMesh(task));
@@ -123,13 +203,13 @@ fn handle_done_meshing_tasks(
             let solid_mesh = computed_mesh.solid_mesh;
             let transparent_mesh = computed_mesh.transparent_mesh;
 
-            if let Some(solid_mesh) = solid_mesh {
+            if let Some(ChunkMesh {
+                mesh: solid_mesh,
+                collider,
+            }) = solid_mesh
+            {
                 if transform.is_some() {
-                    solid_commands.insert((
-                        meshes.add(solid_mesh),
-                        RapierSlowdownWorkaround,
-                        AsyncCollider(ComputedColliderShape::TriMesh),
-                    ));
+                    solid_commands.insert(meshes.add(solid_mesh));
                 } else {
                     solid_commands.insert((
                         MaterialMeshBundle {
@@ -150,10 +230,14 @@ fn handle_done_meshing_tasks(
                                 (ChunkData::edge() / 2) as f32,
                             ),
                         },
-                        RapierSlowdownWorkaround,
-                        AsyncCollider(ComputedColliderShape::TriMesh),
                     ));
                 }
+
+                if let Some(collider) = collider {
+                    solid_commands.insert((RapierSlowdownWorkaround, collider));
+                } else {
+                    solid_commands.remove::<Collider>();
+                }
             } else if transparent_mesh.is_some() {
                 solid_commands.remove::<(Handle<Mesh>, Collider)>();
             } else {
@@ -161,40 +245,49 @@ fn handle_done_meshing_tasks(
                     .remove::<(MaterialMeshBundle<TerrainTextureMaterial>, Collider, Aabb)>();
             }
 
-            solid_commands.remove::<ComputeMesh>();
+            solid_commands
+                .remove::<ComputeMesh>()
+                .insert(computed_mesh.lod);
 
             let transparent_chunk_entity = children.and_then(|children| children.first());
-            if let Some(transparent_mesh) = transparent_mesh {
+            if let Some(ChunkMesh {
+                mesh: transparent_mesh,
+                collider,
+            }) = transparent_mesh
+            {
                 if let Some(transparent_chunk_entity) = transparent_chunk_entity {
                     let mut transparent_commands = commands.entity(*transparent_chunk_entity);
-                    transparent_commands.insert((
-                        meshes.add(transparent_mesh),
-                        AsyncCollider(ComputedColliderShape::TriMesh),
-                    ));
+                    transparent_commands.insert(meshes.add(transparent_mesh));
+                    if let Some(collider) = collider {
+                        transparent_commands.insert(collider);
+                    } else {
+                        transparent_commands.remove::<Collider>();
+                    }
                 } else {
-                    let child = commands
-                        .spawn((
-                            MaterialMeshBundle {
-                                material: terrain_texture.transparent().clone_weak(),
-                                mesh: meshes.add(transparent_mesh),
-                                ..default()
-                            },
-                            AsyncCollider(ComputedColliderShape::TriMesh),
-                            Aabb {
-                                center: Vec3A::new(
-                                    (ChunkData::edge() / 2) as f32,
-                                    (ChunkData::edge() / 2) as f32,
-                                    (ChunkData::edge() / 2) as f32,
-                                ),
-                                half_extents: Vec3A::new(
-                                    (ChunkData::edge() / 2) as f32,
-                                    (ChunkData::edge() / 2) as f32,
-                                    (ChunkData::edge() / 2) as f32,
-                                ),
-                            },
-                            Name::new("Transparent mesh"),
-                        ))
-                        .id();
+                    let mut child_commands = commands.spawn((
+                        MaterialMeshBundle {
+                            material: terrain_texture.transparent().clone_weak(),
+                            mesh: meshes.add(transparent_mesh),
+                            ..default()
+                        },
+                        Aabb {
+                            center: Vec3A::new(
+                                (ChunkData::edge() / 2) as f32,
+                                (ChunkData::edge() / 2) as f32,
+                                (ChunkData::edge() / 2) as f32,
+                            ),
+                            half_extents: Vec3A::new(
+                                (ChunkData::edge() / 2) as f32,
+                                (ChunkData::edge() / 2) as f32,
+                                (ChunkData::edge() / 2) as f32,
+                            ),
+                        },
+                        Name::new("Transparent mesh"),
+                    ));
+                    if let Some(collider) = collider {
+                        child_commands.insert(collider);
+                    }
+                    let child = child_commands.id();
                     commands.entity(chunk_entity).add_child(child);
                 }
             } else {
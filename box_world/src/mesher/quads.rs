@@ -1,17 +1,34 @@
 use super::{
     chunk_boundary::ChunkBoundary,
-    face::{Face, FaceWithAO},
+    face::{face_aos, face_lights, front_neighbor, Face},
+    side::{Axis, Side},
     VoxelVisibility,
 };
+use crate::voxel::{Rotation, Voxel, VoxelRegistry};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Quad {
+    /// Voxel at the minimum corner of the quad
     pub voxel: [u32; 3],
     pub texture_indice: u32,
+    /// Rotation of the voxels from their block state
+    pub rotation: Rotation,
+    /// Combined torch and sun light of each vertex
+    pub lights: [u8; 4],
+    pub aos: [u32; 4],
     pub width: u32,
     pub height: u32,
 }
 
+/// Everything that must match for two faces to be merged into the same quad
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct FaceKey {
+    texture_indice: u32,
+    rotation: Rotation,
+    lights: [u8; 4],
+    aos: [u32; 4],
+}
+
 #[derive(Default)]
 pub struct QuadGroups {
     pub groups: [Vec<Quad>; 6],
@@ -26,13 +43,6 @@ impl QuadGroups {
             .map(|(index, quad)| Face::new(index.into(), quad))
     }
 
-    pub fn iter_with_ao<'a>(
-        &'a self,
-        chunk: &'a ChunkBoundary,
-    ) -> impl Iterator<Item = FaceWithAO<'a>> {
-        self.iter().map(|face| FaceWithAO::new(face, chunk))
-    }
-
     /// Returns the total count of quads across all groups.
     pub fn num_quads(&self) -> usize {
         let mut sum = 0;
@@ -47,75 +57,170 @@ impl QuadGroups {
     }
 }
 
+/// Greedy meshing of visible faces, one slice of the chunk at a time.
+/// Faces are merged when they share texture, light and ambient occlusion.
+/// With smooth lighting, light is averaged around each vertex instead of taken from the front voxel.
+/// Quads are sized with width along Z for X sides and along X otherwise,
+/// and height along Z for Y sides and along Y otherwise.
 pub fn generate_quads_with_buffer(
     solid_pass: bool,
+    smooth_lighting: bool,
     chunk_boundary: &ChunkBoundary,
+    voxel_registry: &VoxelRegistry,
     buffer: &mut QuadGroups,
 ) {
     buffer.clear();
 
-    let x_offset = ChunkBoundary::x_offset();
-    let y_offset = ChunkBoundary::y_offset();
-    let z_offset = ChunkBoundary::z_offset();
+    let edge = chunk_boundary.edge();
+    let mut mask: Vec<Option<FaceKey>> = vec![None; (edge * edge) as usize];
+    let mask_idx = |u: u32, v: u32| (v * edge + u) as usize;
 
-    let voxels = chunk_boundary.voxels();
-    for z in 1..ChunkBoundary::edge() - 1 {
-        for y in 1..ChunkBoundary::edge() - 1 {
-            for x in 1..ChunkBoundary::edge() - 1 {
-                let idx = ChunkBoundary::linearize(x, y, z);
-                let voxel = voxels[idx];
-
-                match voxel.visibility() {
-                    VoxelVisibility::Empty => continue,
-                    visibility => {
-                        let neighbors = [
-                            voxels[idx - x_offset],
-                            voxels[idx + x_offset],
-                            voxels[idx - y_offset],
-                            voxels[idx + y_offset],
-                            voxels[idx - z_offset],
-                            voxels[idx + z_offset],
-                        ];
-
-                        for (i, neighbor) in neighbors.into_iter().enumerate() {
-                            let other = neighbor.visibility();
-
-                            let generate = if solid_pass {
-                                match (visibility, other) {
-                                    (VoxelVisibility::Opaque, VoxelVisibility::Empty)
-                                    | (VoxelVisibility::Opaque, VoxelVisibility::Transparent) => {
-                                        true
-                                    }
-
-                                    (
-                                        VoxelVisibility::Transparent,
-                                        VoxelVisibility::Transparent,
-                                    ) => voxel != neighbor,
-                                    (_, _) => false,
-                                }
-                            } else {
-                                match (visibility, other) {
-                                    (VoxelVisibility::Transparent, VoxelVisibility::Empty) => true,
-                                    (
-                                        VoxelVisibility::Transparent,
-                                        VoxelVisibility::Transparent,
-                                    ) => voxel != neighbor,
-                                    (_, _) => false,
-                                }
-                            };
-
-                            if generate {
-                                buffer.groups[i].push(Quad {
-                                    voxel: [x, y, z],
-                                    texture_indice: voxels[idx].indice(),
-                                    width: 1,
-                                    height: 1,
-                                });
+    for (i, group) in buffer.groups.iter_mut().enumerate() {
+        let side = Side::from(i);
+
+        for slice in 1..=edge {
+            for v in 0..edge {
+                for u in 0..edge {
+                    mask[mask_idx(u, v)] = face_key(
+                        solid_pass,
+                        smooth_lighting,
+                        chunk_boundary,
+                        voxel_registry,
+                        side,
+                        slice_voxel(side, slice, u, v),
+                    );
+                }
+            }
+
+            for v in 0..edge {
+                let mut u = 0;
+                while u < edge {
+                    let Some(key) = mask[mask_idx(u, v)] else {
+                        u += 1;
+                        continue;
+                    };
+
+                    let mut width = 1;
+                    while u + width < edge && mask[mask_idx(u + width, v)] == Some(key) {
+                        width += 1;
+                    }
+
+                    let mut height = 1;
+                    'grow: while v + height < edge {
+                        for du in 0..width {
+                            if mask[mask_idx(u + du, v + height)] != Some(key) {
+                                break 'grow;
                             }
                         }
+                        height += 1;
+                    }
+
+                    for dv in 0..height {
+                        for du in 0..width {
+                            mask[mask_idx(u + du, v + dv)] = None;
+                        }
                     }
+
+                    group.push(Quad {
+                        voxel: slice_voxel(side, slice, u, v),
+                        texture_indice: key.texture_indice,
+                        rotation: key.rotation,
+                        lights: key.lights,
+                        aos: key.aos,
+                        width,
+                        height,
+                    });
+
+                    u += width;
                 }
             }
         }
     }
 }
+
+/// Chunk boundary coordinates of a voxel in a slice perpendicular to the side
+fn slice_voxel(side: Side, slice: u32, u: u32, v: u32) -> [u32; 3] {
+    match side.axis {
+        Axis::X => [slice, v + 1, u + 1],
+        Axis::Y => [u + 1, slice, v + 1],
+        Axis::Z => [u + 1, v + 1, slice],
+    }
+}
+
+fn face_key(
+    solid_pass: bool,
+    smooth_lighting: bool,
+    chunk_boundary: &ChunkBoundary,
+    voxel_registry: &VoxelRegistry,
+    side: Side,
+    voxel_pos: [u32; 3],
+) -> Option<FaceKey> {
+    let [x, y, z] = voxel_pos;
+    let idx = chunk_boundary.linearize(x, y, z);
+    let neighbor_idx = front_neighbor(side, voxel_pos, chunk_boundary);
+
+    let voxels = chunk_boundary.voxels();
+    let voxel = voxels[idx];
+    let neighbor = voxels[neighbor_idx];
+
+    // Other shapes are meshed one voxel at a time
+    if !voxel_registry.get_data(voxel).shape().is_cube()
+        || !face_visible(solid_pass, voxel, neighbor, side, voxel_registry)
+    {
+        return None;
+    }
+
+    let rotation = voxel.state().rotation();
+    Some(FaceKey {
+        texture_indice: voxel_registry
+            .get_data(voxel)
+            .indice(rotation.local_face(side.index())),
+        rotation,
+        lights: if smooth_lighting {
+            face_lights(side, voxel_pos, chunk_boundary, voxel_registry)
+        } else {
+            [chunk_boundary.lights()[neighbor_idx]; 4]
+        },
+        aos: face_aos(side, voxel_pos, chunk_boundary, voxel_registry),
+    })
+}
+
+/// Whether the face of a voxel against its neighbor on `side` is drawn in this pass
+pub(super) fn face_visible(
+    solid_pass: bool,
+    voxel: Voxel,
+    neighbor: Voxel,
+    side: Side,
+    voxel_registry: &VoxelRegistry,
+) -> bool {
+    let visibility = voxel_registry.get_data(voxel).visibility();
+    let other = visibility_through(neighbor, side.opposite(), voxel_registry);
+
+    if solid_pass {
+        match (visibility, other) {
+            (VoxelVisibility::Opaque, VoxelVisibility::Empty)
+            | (VoxelVisibility::Opaque, VoxelVisibility::Transparent) => true,
+
+            (VoxelVisibility::Transparent, VoxelVisibility::Transparent) => voxel != neighbor,
+            (_, _) => false,
+        }
+    } else {
+        match (visibility, other) {
+            (VoxelVisibility::Transparent, VoxelVisibility::Empty) => true,
+            (VoxelVisibility::Transparent, VoxelVisibility::Transparent) => voxel != neighbor,
+            (_, _) => false,
+        }
+    }
+}
+
+/// Visibility of a voxel seen through one of its faces.
+/// Shapes that don't cover the whole face let the faces behind them show, like transparent voxels.
+fn visibility_through(voxel: Voxel, side: Side, voxel_registry: &VoxelRegistry) -> VoxelVisibility {
+    let data = voxel_registry.get_data(voxel);
+    let face = voxel.state().rotation().local_face(side.index());
+    match data.visibility() {
+        VoxelVisibility::Empty => VoxelVisibility::Empty,
+        visibility if data.shape().covers(face) => visibility,
+        _ => VoxelVisibility::Transparent,
+    }
+}
@@ -1,7 +1,11 @@
 use bevy::{
     pbr::StandardMaterialFlags,
     prelude::*,
-    render::{mesh::MeshVertexAttribute, render_resource::*},
+    render::{
+        mesh::MeshVertexAttribute,
+        render_resource::*,
+        texture::{ImageAddressMode, ImageSampler, ImageSamplerDescriptor},
+    },
 };
 use bevy_asset_loader::prelude::*;
 
@@ -26,6 +30,18 @@ impl FromWorld for TerrainMaterial {
         let mut materials = cell
             .get_resource_mut::<Assets<TerrainTextureMaterial>>()
             .expect("Unable to get Assets<TerrainTextureMaterial>");
+        let mut images = cell
+            .get_resource_mut::<Assets<Image>>()
+            .expect("Unable to get Assets<Image>");
+
+        // Greedy meshed quads span several voxels, so the texture repeats across them
+        if let Some(image) = images.get_mut(&terrain_texture.terrain_handle) {
+            image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+                address_mode_u: ImageAddressMode::Repeat,
+                address_mode_v: ImageAddressMode::Repeat,
+                ..ImageSamplerDescriptor::nearest()
+            });
+        }
 
         info!("Loading TerrainTextureMaterial");
 
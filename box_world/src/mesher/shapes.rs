@@ -0,0 +1,151 @@
+use crate::voxel::{BlockShape, ShapeBox, VoxelRegistry, VoxelVisibility};
+
+use super::{
+    chunk_boundary::ChunkBoundary,
+    face::{front_neighbor, unit_face},
+    quads::face_visible,
+    side::Side,
+};
+
+/// Texture coordinates of the corners of a whole voxel face, in the vertex order of `unit_face`
+const UNIT_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [0.0, 0.0], [1.0, 0.0]];
+
+/// Face of a voxel that isn't a full cube, too irregular for greedy meshing
+pub struct ShapeFace {
+    /// Corners in voxels from the chunk origin, in the vertex order of `unit_face`
+    pub positions: [[f32; 3]; 4],
+    pub normal: [f32; 3],
+    pub uvs: [[f32; 2]; 4],
+    pub texture_indice: u32,
+    /// Combined torch and sun light of the voxel in front of the face
+    pub light: u8,
+    pub collides: bool,
+}
+
+/// Faces of the slabs, stairs, crosses and custom shapes of the chunk drawn in this pass,
+/// rotated by the voxel state. Box faces against a neighbor are culled like cube faces,
+/// faces inside the voxel are always drawn.
+pub fn generate_shape_faces(
+    solid_pass: bool,
+    chunk_boundary: &ChunkBoundary,
+    voxel_registry: &VoxelRegistry,
+    faces: &mut Vec<ShapeFace>,
+) {
+    faces.clear();
+
+    let voxels = chunk_boundary.voxels();
+    let lights = chunk_boundary.lights();
+    let edge = chunk_boundary.edge();
+    for z in 1..=edge {
+        for y in 1..=edge {
+            for x in 1..=edge {
+                let voxel_pos = [x, y, z];
+                let idx = chunk_boundary.linearize(x, y, z);
+                let voxel = voxels[idx];
+                let data = voxel_registry.get_data(voxel);
+
+                let in_pass = match data.visibility() {
+                    VoxelVisibility::Opaque => solid_pass,
+                    VoxelVisibility::Transparent => !solid_pass,
+                    VoxelVisibility::Empty => false,
+                };
+                if !in_pass || data.shape().is_cube() {
+                    continue;
+                }
+
+                let origin = [(x - 1) as f32, (y - 1) as f32, (z - 1) as f32];
+                if *data.shape() == BlockShape::Cross {
+                    faces.extend(cross_faces(origin, data.indice(0), lights[idx]));
+                    continue;
+                }
+
+                let rotation = voxel.state().rotation();
+                for shape_box in data.shape().boxes() {
+                    for face in 0..6 {
+                        let side = Side::from(rotation.rotate_face(face));
+                        let light = if shape_box.touches(face) {
+                            let neighbor_idx = front_neighbor(side, voxel_pos, chunk_boundary);
+                            let neighbor = voxels[neighbor_idx];
+                            if !face_visible(solid_pass, voxel, neighbor, side, voxel_registry) {
+                                continue;
+                            }
+                            lights[neighbor_idx]
+                        } else {
+                            lights[idx]
+                        };
+
+                        let (positions, uvs) = box_face(Side::from(face), &shape_box);
+                        faces.push(ShapeFace {
+                            positions: positions.map(|position| {
+                                let position = rotation.rotate_point(position);
+                                [0, 1, 2].map(|axis| origin[axis] + position[axis])
+                            }),
+                            normal: side.normal(),
+                            uvs,
+                            texture_indice: data.indice(face),
+                            light,
+                            collides: data.shape().has_collisions(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Corners and texture coordinates of the face of a box on a side.
+/// The texture is cropped to the part of the voxel face the box covers.
+fn box_face(side: Side, shape_box: &ShapeBox) -> ([[f32; 3]; 4], [[f32; 2]; 4]) {
+    let unit = unit_face(side);
+    let positions = unit.map(|corner| {
+        [0, 1, 2].map(|axis| {
+            shape_box.min[axis] + corner[axis] * (shape_box.max[axis] - shape_box.min[axis])
+        })
+    });
+
+    // The first corner of a face differs from the second along one axis
+    // and from the third along another, the texture runs along those two axes
+    let differs = |other: [f32; 3]| (0..3).find(|axis| unit[0][*axis] != other[*axis]).unwrap();
+    let (u_axis, v_axis) = (differs(unit[1]), differs(unit[2]));
+    let uvs = positions.map(|position| {
+        let along_u = (position[u_axis] - unit[0][u_axis]).abs();
+        let along_v = (position[v_axis] - unit[0][v_axis]).abs();
+        [0, 1].map(|i| {
+            UNIT_UVS[0][i]
+                + (UNIT_UVS[1][i] - UNIT_UVS[0][i]) * along_u
+                + (UNIT_UVS[2][i] - UNIT_UVS[0][i]) * along_v
+        })
+    });
+
+    (positions, uvs)
+}
+
+/// Two diagonal planes, each drawn from both sides
+fn cross_faces(origin: [f32; 3], texture_indice: u32, light: u8) -> Vec<ShapeFace> {
+    let planes = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 1.0]],
+        [[1.0, 0.0, 1.0], [0.0, 0.0, 0.0]],
+        [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+        [[0.0, 0.0, 1.0], [1.0, 0.0, 0.0]],
+    ];
+
+    planes
+        .into_iter()
+        .map(|[left, right]| {
+            let corners = [
+                left,
+                right,
+                [left[0], 1.0, left[2]],
+                [right[0], 1.0, right[2]],
+            ];
+            ShapeFace {
+                positions: corners.map(|corner| [0, 1, 2].map(|axis| origin[axis] + corner[axis])),
+                normal: [0.0, 1.0, 0.0],
+                uvs: UNIT_UVS,
+                texture_indice,
+                light,
+                collides: false,
+            }
+        })
+        .collect()
+}
@@ -37,6 +37,22 @@ impl Side {
     pub fn normals(&self) -> [[f32; 3]; 4] {
         [self.normal(), self.normal(), self.normal(), self.normal()]
     }
+
+    pub fn opposite(&self) -> Side {
+        Side::new(self.axis, !self.positive)
+    }
+
+    /// Inverse of `Side::from(usize)`
+    pub fn index(&self) -> usize {
+        match (&self.axis, &self.positive) {
+            (Axis::X, false) => 0,
+            (Axis::X, true) => 1,
+            (Axis::Y, false) => 2,
+            (Axis::Y, true) => 3,
+            (Axis::Z, false) => 4,
+            (Axis::Z, true) => 5,
+        }
+    }
 }
 
 // Note: it is important this matches the neighbor ordering from `simple_mesh`
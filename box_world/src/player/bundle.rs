@@ -20,6 +20,7 @@ impl PreSpawnPlayerBundle {
             load_point: LoadPoint {
                 horizontal: horizontal_view_distance,
                 vertical: vertical_view_distance,
+                ..default()
             },
             spatial: SpatialBundle {
                 transform: Transform::from_translation(position),
@@ -6,7 +6,7 @@ use bevy::{
 use crate::{
     chunk::{ChunkData, LoadedChunks, VoxelAddedEvent, VoxelRemovedEvent},
     mesher::NeedsMesh,
-    voxel::{GlobalVoxelPos, Voxel, VoxelRegistry},
+    voxel::{BlockState, GlobalVoxelPos, StateProperty, Voxel, VoxelData, VoxelRegistry},
 };
 
 use super::Player;
@@ -27,6 +27,10 @@ pub(super) fn change_current_block(
         current_block.0 = Some(voxel_registry.get_voxel("glass"));
     } else if keyboard_input.just_pressed(KeyCode::Key4) {
         current_block.0 = Some(voxel_registry.get_voxel("torch"));
+    } else if keyboard_input.just_pressed(KeyCode::Key5) {
+        current_block.0 = Some(voxel_registry.get_voxel("log"));
+    } else if keyboard_input.just_pressed(KeyCode::Key6) {
+        current_block.0 = Some(voxel_registry.get_voxel("stone_stairs"));
     }
 }
 
@@ -37,6 +41,7 @@ pub(super) fn interact(
     loaded_chunks: Res<LoadedChunks>,
     mouse_input: Res<Input<MouseButton>>,
     current_block: Res<CurrentBlock>,
+    voxel_registry: Res<VoxelRegistry>,
     window: Query<&Window, With<PrimaryWindow>>,
     player_position: Query<&Transform, With<Player>>,
     camera: Query<(&Camera, &GlobalTransform)>,
@@ -77,8 +82,8 @@ pub(super) fn interact(
                 continue;
             };
 
-            if !chunk_data
-                .get(local_pos.x, local_pos.y, local_pos.z)
+            if !voxel_registry
+                .get_data(chunk_data.get(local_pos.x, local_pos.y, local_pos.z))
                 .is_empty()
             {
                 // Highlight selected block
@@ -101,19 +106,20 @@ pub(super) fn interact(
                         return;
                     };
 
-                    // Place in previous spot
-                    // FIXME: Place on selected face, not just previous ray voxel
-                    let mut prev_voxel_pos = voxel_pos;
-
-                    // Rewind ray backwards by one voxel
-                    for j in 1..i {
-                        let prev_ray_pos = ray.get_point((i - j) as f32 * RAY_STEP);
-                        prev_voxel_pos = GlobalVoxelPos::from_global_coords(prev_ray_pos);
-
-                        if prev_voxel_pos != voxel_pos {
-                            break;
-                        }
-                    }
+                    // Place against the face the ray entered the selected voxel through
+                    let face = hit_face(ray, voxel_pos);
+                    let mut offset = IVec3::ZERO;
+                    offset[face / 2] = if face % 2 == 1 { 1 } else { -1 };
+                    let prev_voxel_pos = GlobalVoxelPos::new(
+                        voxel_pos.x + offset.x,
+                        voxel_pos.y + offset.y,
+                        voxel_pos.z + offset.z,
+                    );
+                    let player_equipped_block = player_equipped_block.with_state(placed_state(
+                        voxel_registry.get_data(player_equipped_block),
+                        face,
+                        ray.direction,
+                    ));
 
                     // Can't place on top of the player
                     if prev_voxel_pos == player_head_pos || prev_voxel_pos == player_feet_pos {
@@ -177,3 +183,51 @@ pub(super) fn interact(
         }
     }
 }
+
+/// Face of a voxel a ray enters it through, ordered X-, X+, Y-, Y+, Z-, Z+
+fn hit_face(ray: Ray, voxel_pos: GlobalVoxelPos) -> usize {
+    let min = voxel_pos.to_global_coords();
+    (0..3)
+        .map(|axis| {
+            let direction = ray.direction[axis];
+            // Going towards positive coordinates enters through the negative face
+            let (plane, face) = if direction > 0.0 {
+                (min[axis], axis * 2)
+            } else {
+                (min[axis] + 1.0, axis * 2 + 1)
+            };
+            // The face entered last is the one on the voxel, a parallel ray enters through neither
+            let distance = if direction == 0.0 {
+                f32::NEG_INFINITY
+            } else {
+                (plane - ray.origin[axis]) / direction
+            };
+            (distance, face)
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap()
+        .1
+}
+
+/// State of a voxel placed against `face` of another one, for the properties its block declares
+fn placed_state(data: &VoxelData, face: usize, look: Vec3) -> BlockState {
+    let mut state = BlockState::default();
+
+    if data.has_property(StateProperty::Facing) {
+        // Away from the player, so into the wall when placed against one
+        let facing = if face / 2 != 1 {
+            face ^ 1
+        } else if look.x.abs() > look.z.abs() {
+            (look.x > 0.0) as usize
+        } else {
+            4 + (look.z > 0.0) as usize
+        };
+        state = state.with_facing(facing);
+    }
+
+    if data.has_property(StateProperty::Axis) {
+        state = state.with_axis(face / 2);
+    }
+
+    state
+}
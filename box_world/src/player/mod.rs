@@ -1,7 +1,8 @@
 use crate::{
-    chunk::{ChunkData, LoadPoint},
+    chunk::{ChunkData, Database, LoadPoint, PlayerState, WorldMeta},
     states::GameStates,
     voxel::GlobalVoxelPos,
+    world_generator::ActiveGenerator,
     HORIZONTAL_VIEW_DISTANCE, VERTICAL_VIEW_DISTANCE,
 };
 use bevy::{
@@ -41,7 +42,10 @@ impl Plugin for PlayerPlugin {
 
         app.add_systems(Startup, spawn_player_load_point);
 
-        app.add_systems(OnEnter(GameStates::InGame), spawn_player_cam_and_collider);
+        app.add_systems(
+            OnEnter(GameStates::InGame),
+            (spawn_player_cam_and_collider, log_spawn_biome),
+        );
 
         app.add_systems(
             Update,
@@ -50,6 +54,7 @@ impl Plugin for PlayerPlugin {
                 movement::movement_collision,
                 input::interact.after(movement::movement_collision),
                 input::change_current_block,
+                update_player_state.after(movement::movement_collision),
             )
                 .run_if(in_state(GameStates::InGame)),
         );
@@ -59,21 +64,61 @@ impl Plugin for PlayerPlugin {
 #[derive(Component, Default)]
 pub struct Player;
 
-fn spawn_player_load_point(mut commands: Commands) {
+fn spawn_player_load_point(
+    mut commands: Commands,
+    database: Res<Database>,
+    world_meta: Res<WorldMeta>,
+) {
+    // Players come back where they left, new players start at the world spawn
+    let player_state = database.load_player().unwrap_or_else(|| {
+        let camera = FPSCamera::default();
+        PlayerState {
+            position: world_meta.spawn_point.as_vec3().to_array(),
+            phi: camera.phi,
+            theta: camera.theta,
+        }
+    });
+    let player_pos = Vec3::from_array(player_state.position);
+
     // Initially only load a small area around the player for speed
     // We will load to view distance after spawning
-    let player_pos = GlobalVoxelPos::new(5000, 200, 5000);
-    commands.spawn(bundle::PreSpawnPlayerBundle::new(
-        16,
-        10,
-        player_pos.as_vec3(),
+    commands.spawn((
+        bundle::PreSpawnPlayerBundle::new(16, 10, player_pos),
+        player_state,
     ));
 }
 
+fn log_spawn_biome(generator: Res<ActiveGenerator>, player: Query<&Transform, With<Player>>) {
+    let Ok(transform) = player.get_single() else {
+        return;
+    };
+    if let Some(biome) = generator
+        .0
+        .biome_at(GlobalVoxelPos::from_global_coords(transform.translation))
+    {
+        info!("Spawned player in biome {:?}", biome);
+    }
+}
+
+/// Keep the saved state of the player up to date
+fn update_player_state(
+    mut player: Query<(&Transform, &mut PlayerState), With<Player>>,
+    camera: Query<&FPSCamera>,
+) {
+    let (Ok((transform, mut player_state)), Ok(camera)) =
+        (player.get_single_mut(), camera.get_single())
+    else {
+        return;
+    };
+    player_state.position = transform.translation.to_array();
+    player_state.phi = camera.phi;
+    player_state.theta = camera.theta;
+}
+
 pub fn spawn_player_cam_and_collider(
     mut commands: Commands,
     cameras: Query<Entity, With<Camera>>,
-    player: Query<Entity, With<Player>>,
+    player: Query<(Entity, &PlayerState), With<Player>>,
     mut windows: Query<&mut Window, With<PrimaryWindow>>,
 ) {
     cameras
@@ -100,12 +145,13 @@ pub fn spawn_player_cam_and_collider(
         }
     };
 
-    let player_entity = player.single();
+    let (player_entity, player_state) = player.single();
     commands
         .entity(player_entity)
         .insert(LoadPoint {
             horizontal: HORIZONTAL_VIEW_DISTANCE,
             vertical: VERTICAL_VIEW_DISTANCE,
+            ..default()
         })
         .insert(bundle::PostSpawnPlayerBundle::default())
         .with_children(|c| {
@@ -117,7 +163,11 @@ pub fn spawn_player_cam_and_collider(
                 CollisionGroups::new(Group::GROUP_1, Group::GROUP_2),
             ));
             c.spawn((
-                FPSCamera::default(),
+                FPSCamera {
+                    phi: player_state.phi,
+                    theta: player_state.theta,
+                    ..default()
+                },
                 camera,
                 AtmosphereCamera::default(),
                 FogSettings {
@@ -19,6 +19,8 @@ use super::{
 const PLAYER_JUMP_SPEED: f32 = 10.0;
 const PLAYER_RUN_SPEED: f32 = 5.0;
 const PLAYER_SPRINT_MOD: f32 = 2.0;
+/// How far the body is lifted over a low obstacle, the legs then climb the rest like on slabs and stairs
+const STEP_HEIGHT: f32 = 0.3;
 
 #[allow(clippy::too_many_arguments)]
 pub(super) fn movement_input(
@@ -148,6 +150,7 @@ pub(super) fn movement_collision(
             predicate: None,
         };
 
+        let mut stepped = false;
         loop {
             if movement_left.length() <= 0.0 {
                 break;
@@ -179,6 +182,39 @@ pub(super) fn movement_collision(
                         break;
                     }
                     if let Some(details) = toi.details {
+                        // Blocked by a wall, step up if it is low enough to clear
+                        let step = Vec3::new(0.0, STEP_HEIGHT, 0.0);
+                        let horizontal = Vec3::new(movement_left.x, 0.0, movement_left.z);
+                        if !stepped
+                            && details.normal1.y.abs() < 0.1
+                            && horizontal.length() > 0.0
+                            && rapier_context
+                                .cast_shape(
+                                    position,
+                                    Rot::default(),
+                                    step,
+                                    &shape,
+                                    1.0,
+                                    true,
+                                    filter,
+                                )
+                                .is_none()
+                            && rapier_context
+                                .cast_shape(
+                                    position + step,
+                                    Rot::default(),
+                                    horizontal,
+                                    &shape,
+                                    1.0,
+                                    true,
+                                    filter,
+                                )
+                                .is_none()
+                        {
+                            stepped = true;
+                            player_transform.translation += step;
+                            continue;
+                        }
                         movement_left -= movement_left.dot(details.normal1) * details.normal1;
                     }
                     fps_camera.velocity = movement_left / time.delta().as_secs_f32();
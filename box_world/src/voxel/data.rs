@@ -4,30 +4,74 @@ use bevy::{
 };
 use serde::{Deserialize, Serialize};
 
+use super::{BlockShape, BlockState, StateProperty};
 use crate::mesher::VoxelVisibility;
 
+/// Bits of a voxel taken by its id, the state takes the rest
+const ID_BITS: u32 = u16::BITS - BlockState::BITS;
+const ID_MASK: u16 = (1 << ID_BITS) - 1;
+
+/// Persistent numeric id of a voxel type and the state of this voxel, as stored in chunks.
+/// The id takes the low bits so voxels saved before states existed read back unrotated.
+/// Properties are looked up through the `VoxelRegistry`.
+/// Id 0 is always air.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Voxel(u16);
+
+impl Voxel {
+    /// Largest id a voxel type can have
+    pub const MAX_ID: u16 = ID_MASK;
+
+    pub const fn new(id: u16) -> Self {
+        Self(id & ID_MASK)
+    }
+
+    pub fn id(&self) -> u16 {
+        self.0 & ID_MASK
+    }
+
+    pub fn state(&self) -> BlockState {
+        BlockState::from_bits((self.0 >> ID_BITS) as u8)
+    }
+
+    pub fn with_state(self, state: BlockState) -> Self {
+        Self(self.id() | (state.bits() as u16) << ID_BITS)
+    }
+
+    pub fn is_air(&self) -> bool {
+        self.id() == 0
+    }
+}
+
+/// Properties of a voxel type, loaded from the generated block data files
 #[allow(dead_code)]
-#[derive(
-    Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TypeUuid, Asset, TypePath,
-)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TypeUuid, Asset, TypePath)]
 #[uuid = "2f63c7be-0955-40b0-8b5f-845a5f3eba9a"]
-pub struct Voxel {
+pub struct VoxelData {
     visibility: VoxelVisibility,
-    texture_id: u16,
+    /// Texture of each face, ordered X-, X+, Y-, Y+, Z-, Z+
+    texture_ids: [u16; 6],
     emissiveness: u8,
+    #[serde(default)]
+    shape: BlockShape,
+    /// State properties placed voxels of this type can take
+    #[serde(default)]
+    properties: Vec<StateProperty>,
 }
 
-impl Voxel {
+impl VoxelData {
     pub fn is_empty(&self) -> bool {
         self.visibility == VoxelVisibility::Empty
     }
 
+    /// Opaque full cube, blocking light and hiding the faces around it
     pub fn is_opaque(&self) -> bool {
-        self.visibility == VoxelVisibility::Opaque
+        self.visibility == VoxelVisibility::Opaque && self.shape.is_cube()
     }
 
-    pub fn indice(&self) -> u32 {
-        self.texture_id as u32
+    /// Texture of a face, `face` being the index of its side in the mesher order
+    pub fn indice(&self, face: usize) -> u32 {
+        self.texture_ids[face] as u32
     }
 
     pub fn visibility(&self) -> VoxelVisibility {
@@ -37,4 +81,22 @@ impl Voxel {
     pub fn emissiveness(&self) -> u8 {
         self.emissiveness
     }
+
+    pub fn shape(&self) -> &BlockShape {
+        &self.shape
+    }
+
+    pub fn has_property(&self, property: StateProperty) -> bool {
+        self.properties.contains(&property)
+    }
+}
+
+#[cfg(test)]
+impl VoxelData {
+    pub fn with_visibility(visibility: VoxelVisibility) -> Self {
+        Self {
+            visibility,
+            ..Default::default()
+        }
+    }
 }
@@ -3,13 +3,17 @@ use bevy::prelude::{App, Plugin};
 mod data;
 mod position;
 mod registry;
+mod shape;
+mod state;
 
 use bevy_asset_loader::prelude::*;
 use bevy_common_assets::ron::RonAssetPlugin;
-pub use data::Voxel;
+pub use data::{Voxel, VoxelData};
 pub use position::ChunkLocalVoxelPos;
 pub use position::GlobalVoxelPos;
 pub use registry::VoxelRegistry;
+pub use shape::{BlockShape, ShapeBox};
+pub use state::{BlockState, Rotation, StateProperty};
 
 use crate::states::GameStates;
 
@@ -17,7 +21,7 @@ pub struct VoxelPlugin;
 
 impl Plugin for VoxelPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(RonAssetPlugin::<Voxel>::new(&["voxel.ron"]));
+        app.add_plugins(RonAssetPlugin::<VoxelData>::new(&["voxel.ron"]));
 
         app.add_collection_to_loading_state::<_, registry::VoxelDataAssets>(
             GameStates::AssetLoading,
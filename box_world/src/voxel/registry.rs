@@ -3,29 +3,35 @@ use std::sync::Arc;
 use bevy::{prelude::*, utils::HashMap};
 use bevy_asset_loader::prelude::*;
 
-use super::Voxel;
+use super::{Voxel, VoxelData};
+use crate::chunk::Database;
 
 #[derive(AssetCollection, Resource)]
 pub struct VoxelDataAssets {
     #[asset(path = "data/blocks", collection(typed, mapped))]
-    data_mapped: HashMap<String, Handle<Voxel>>,
+    data_mapped: HashMap<String, Handle<VoxelData>>,
 }
 
 #[derive(Resource, Clone)]
 pub struct VoxelRegistry {
     correspondance: Arc<HashMap<String, Voxel>>,
+    /// Voxel properties, indexed by voxel id
+    data: Arc<Vec<VoxelData>>,
 }
 
 impl FromWorld for VoxelRegistry {
     fn from_world(world: &mut World) -> Self {
         let voxels = world
-            .get_resource::<Assets<Voxel>>()
-            .expect("Failed to get Assets<Voxel>");
+            .get_resource::<Assets<VoxelData>>()
+            .expect("Failed to get Assets<VoxelData>");
         let voxel_assets = world
             .get_resource::<VoxelDataAssets>()
             .expect("Failed to get VoxelDataAssets");
+        let database = world
+            .get_resource::<Database>()
+            .expect("Failed to get Database");
 
-        let correspondance: HashMap<String, Voxel> = voxel_assets
+        let definitions: HashMap<String, VoxelData> = voxel_assets
             .data_mapped
             .iter()
             .map(|(key, value)| {
@@ -33,19 +39,49 @@ impl FromWorld for VoxelRegistry {
                 key = key.split('.').collect::<Vec<_>>()[0];
                 key = key.split('\\').last().unwrap();
                 key = key.split('/').last().unwrap();
-                (key.to_string(), *voxels.get(value).unwrap())
+                (key.to_string(), voxels.get(value).unwrap().clone())
             })
             .collect();
 
+        // Reuse the ids saved in the world so existing chunks keep their meaning
+        let names: Vec<&str> = definitions.keys().map(String::as_str).collect();
+        let ids = database.reserve_voxel_ids(&names);
+        let next_id = ids.values().max().map_or(0, |id| id + 1);
+        assert!(
+            next_id <= Voxel::MAX_ID + 1,
+            "Too many voxel types, ids go up to {}",
+            Voxel::MAX_ID
+        );
+
+        let mut data = vec![VoxelData::default(); next_id as usize];
+        let mut correspondance = HashMap::new();
+        for (name, id) in ids.into_iter() {
+            if let Some(definition) = definitions.get(&name) {
+                data[id as usize] = definition.clone();
+                correspondance.insert(name, Voxel::new(id));
+            } else {
+                warn!(
+                    "Voxel {} is used by this world but has no definition, it will be treated as air",
+                    name
+                );
+            }
+        }
+
         dbg!(&correspondance);
 
         Self {
             correspondance: Arc::new(correspondance),
+            data: Arc::new(data),
         }
     }
 }
 
 impl VoxelRegistry {
+    /// Voxel with this name, if it has a definition
+    pub fn find_voxel(&self, name: &str) -> Option<Voxel> {
+        self.correspondance.get(name).copied()
+    }
+
     pub fn get_voxel(&self, name: &str) -> Voxel {
         if let Some(voxel) = self.correspondance.get(name) {
             *voxel
@@ -56,4 +92,40 @@ impl VoxelRegistry {
             );
         }
     }
+
+    /// Every voxel with a definition, ordered by id
+    pub fn voxels(&self) -> Vec<Voxel> {
+        let mut voxels: Vec<Voxel> = self.correspondance.values().copied().collect();
+        voxels.sort_unstable_by_key(|voxel| voxel.id());
+        voxels
+    }
+
+    pub fn get_data(&self, voxel: Voxel) -> &VoxelData {
+        if let Some(data) = self.data.get(voxel.id() as usize) {
+            data
+        } else {
+            panic!(
+                "Failed to access VoxelData with id {}. Id doesn't exist",
+                voxel.id()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+impl VoxelRegistry {
+    /// Registry without assets or world, ids follow the order of `voxels` starting with air
+    pub fn from_data(voxels: Vec<(&str, VoxelData)>) -> Self {
+        let correspondance = voxels
+            .iter()
+            .enumerate()
+            .map(|(id, (name, _data))| (name.to_string(), Voxel::new(id as u16)))
+            .collect();
+        let data = voxels.into_iter().map(|(_name, data)| data).collect();
+
+        Self {
+            correspondance: Arc::new(correspondance),
+            data: Arc::new(data),
+        }
+    }
 }
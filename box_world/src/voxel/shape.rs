@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+/// Box inside a voxel, in voxel units from 0.0 to 1.0 on each axis
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShapeBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl ShapeBox {
+    pub const FULL: ShapeBox = ShapeBox::new([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]);
+
+    pub const fn new(min: [f32; 3], max: [f32; 3]) -> Self {
+        Self { min, max }
+    }
+
+    /// Whether the box reaches the voxel face with this index, ordered X-, X+, Y-, Y+, Z-, Z+
+    pub fn touches(&self, face: usize) -> bool {
+        let axis = face / 2;
+        if face % 2 == 1 {
+            self.max[axis] >= 1.0
+        } else {
+            self.min[axis] <= 0.0
+        }
+    }
+
+    /// Whether the box touches a face and spans all of it
+    pub fn covers(&self, face: usize) -> bool {
+        let axis = face / 2;
+        self.touches(face)
+            && (0..3)
+                .filter(|other| *other != axis)
+                .all(|other| self.min[other] <= 0.0 && self.max[other] >= 1.0)
+    }
+}
+
+/// Geometry of a voxel type
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum BlockShape {
+    #[default]
+    Cube,
+    /// Lower half of the voxel
+    Slab,
+    /// Slab with a step on its Z+ half
+    Stair,
+    /// Two diagonal planes crossing in the middle, for plants. Has no collisions.
+    Cross,
+    Boxes(Vec<ShapeBox>),
+}
+
+impl BlockShape {
+    pub fn is_cube(&self) -> bool {
+        *self == BlockShape::Cube
+    }
+
+    /// Boxes making up the shape, empty for a cross
+    pub fn boxes(&self) -> Vec<ShapeBox> {
+        match self {
+            BlockShape::Cube => vec![ShapeBox::FULL],
+            BlockShape::Slab => vec![ShapeBox::new([0.0, 0.0, 0.0], [1.0, 0.5, 1.0])],
+            BlockShape::Stair => vec![
+                ShapeBox::new([0.0, 0.0, 0.0], [1.0, 0.5, 1.0]),
+                ShapeBox::new([0.0, 0.5, 0.5], [1.0, 1.0, 1.0]),
+            ],
+            BlockShape::Cross => Vec::new(),
+            BlockShape::Boxes(boxes) => boxes.clone(),
+        }
+    }
+
+    /// Whether one of the boxes covers the whole voxel face with this index,
+    /// so the face of the neighbor against it is hidden
+    pub fn covers(&self, face: usize) -> bool {
+        match self {
+            BlockShape::Cube => true,
+            BlockShape::Cross => false,
+            _ => self.boxes().iter().any(|shape_box| shape_box.covers(face)),
+        }
+    }
+
+    pub fn has_collisions(&self) -> bool {
+        *self != BlockShape::Cross
+    }
+}
@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+
+/// Property of a block that changes from one placed voxel to another, declared in the block data files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StateProperty {
+    /// Horizontal direction the Z+ side of the block points to, away from the player placing it
+    Facing,
+    /// Axis the Y axis of the block runs along, like logs
+    Axis,
+    /// Liquid fills the space around the block shape
+    Waterlogged,
+}
+
+const FACING_MASK: u8 = 0b11;
+const AXIS_SHIFT: u8 = 2;
+const AXIS_MASK: u8 = 0b11 << AXIS_SHIFT;
+const WATERLOGGED_BIT: u8 = 1 << 4;
+
+/// Faces the Z+ side of a block points to after each quarter turn around Y
+const FACINGS: [usize; 4] = [5, 1, 4, 0];
+
+/// State of a single voxel, packed with its id so chunk palettes store each state once.
+/// Properties a block doesn't declare stay at zero, the unrotated state.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BlockState(u8);
+
+#[allow(dead_code)]
+impl BlockState {
+    /// Bits taken by a state
+    pub const BITS: u32 = 5;
+
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Face the Z+ side of the block points to, ordered X-, X+, Y-, Y+, Z-, Z+
+    pub fn facing(self) -> usize {
+        FACINGS[(self.0 & FACING_MASK) as usize]
+    }
+
+    /// Panics if the face isn't horizontal
+    pub fn with_facing(self, face: usize) -> Self {
+        let turns = FACINGS
+            .iter()
+            .position(|facing| *facing == face)
+            .expect("Blocks can only face horizontally") as u8;
+        Self((self.0 & !FACING_MASK) | turns)
+    }
+
+    /// Axis the Y axis of the block runs along, 0 to 2 for X, Y and Z
+    pub fn axis(self) -> usize {
+        match (self.0 & AXIS_MASK) >> AXIS_SHIFT {
+            1 => 0,
+            2 => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn with_axis(self, axis: usize) -> Self {
+        let bits = match axis {
+            0 => 1,
+            2 => 2,
+            _ => 0,
+        };
+        Self((self.0 & !AXIS_MASK) | bits << AXIS_SHIFT)
+    }
+
+    pub fn waterlogged(self) -> bool {
+        self.0 & WATERLOGGED_BIT != 0
+    }
+
+    pub fn with_waterlogged(self, waterlogged: bool) -> Self {
+        if waterlogged {
+            Self(self.0 | WATERLOGGED_BIT)
+        } else {
+            Self(self.0 & !WATERLOGGED_BIT)
+        }
+    }
+
+    /// Rotation from block space to world space, the axis first then the facing
+    pub fn rotation(self) -> Rotation {
+        let axis = match self.axis() {
+            0 => Rotation::Y_TO_X,
+            2 => Rotation::Y_TO_Z,
+            _ => Rotation::IDENTITY,
+        };
+        (0..self.0 & FACING_MASK).fold(axis, |rotation, _| rotation.then(Rotation::QUARTER_TURN))
+    }
+}
+
+/// Rotation of a voxel around its center by quarter turns, as a matrix of -1, 0 and 1
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Rotation([[i8; 3]; 3]);
+
+impl Rotation {
+    pub const IDENTITY: Rotation = Rotation([[1, 0, 0], [0, 1, 0], [0, 0, 1]]);
+    /// Around Y, from Z+ to X+
+    const QUARTER_TURN: Rotation = Rotation([[0, 0, 1], [0, 1, 0], [-1, 0, 0]]);
+    /// Around Z, from Y+ to X+
+    const Y_TO_X: Rotation = Rotation([[0, 1, 0], [-1, 0, 0], [0, 0, 1]]);
+    /// Around X, from Y+ to Z+
+    const Y_TO_Z: Rotation = Rotation([[1, 0, 0], [0, 0, -1], [0, 1, 0]]);
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+
+    /// This rotation followed by `other`
+    fn then(self, other: Rotation) -> Rotation {
+        Rotation([0, 1, 2].map(|row| {
+            [0, 1, 2].map(|column| (0..3).map(|i| other.0[row][i] * self.0[i][column]).sum())
+        }))
+    }
+
+    pub fn inverse(self) -> Rotation {
+        Rotation([0, 1, 2].map(|row| [0, 1, 2].map(|column| self.0[column][row])))
+    }
+
+    fn apply(&self, vector: [f32; 3]) -> [f32; 3] {
+        self.0
+            .map(|row| (0..3).map(|i| row[i] as f32 * vector[i]).sum())
+    }
+
+    /// Rotate a point given in voxel units around the center of the voxel at the origin
+    pub fn rotate_point(&self, point: [f32; 3]) -> [f32; 3] {
+        self.apply(point.map(|coord| coord - 0.5))
+            .map(|coord| coord + 0.5)
+    }
+
+    /// Face a block face ends up on, both ordered X-, X+, Y-, Y+, Z-, Z+
+    pub fn rotate_face(&self, face: usize) -> usize {
+        let mut normal = [0.0; 3];
+        normal[face / 2] = if face % 2 == 1 { 1.0 } else { -1.0 };
+
+        let normal = self.apply(normal);
+        let axis = (0..3).find(|axis| normal[*axis] != 0.0).unwrap();
+        axis * 2 + (normal[axis] > 0.0) as usize
+    }
+
+    /// Block face shown on a face of the voxel
+    pub fn local_face(&self, face: usize) -> usize {
+        self.inverse().rotate_face(face)
+    }
+}
@@ -0,0 +1,159 @@
+use noise::{Fbm, MultiFractal, NoiseFn, OpenSimplex};
+
+use crate::voxel::GlobalVoxelPos;
+
+/// Horizontal distance over which temperature and humidity change noticeably
+const CLIMATE_SCALE: f64 = 1000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Plains,
+    Mountains,
+    SnowyPeaks,
+    Desert,
+    Tundra,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 5] = [
+        Biome::Plains,
+        Biome::Mountains,
+        Biome::SnowyPeaks,
+        Biome::Desert,
+        Biome::Tundra,
+    ];
+
+    pub fn params(&self) -> &'static BiomeParams {
+        match self {
+            Biome::Plains => &BiomeParams {
+                temperature: 0.2,
+                humidity: 0.0,
+                surface: "grass",
+                subsurface: "dirt",
+                subsurface_depth: 3,
+                height_scale: 0.4,
+                blend: 0.4,
+            },
+            Biome::Mountains => &BiomeParams {
+                temperature: 0.0,
+                humidity: 0.6,
+                surface: "stone",
+                subsurface: "stone",
+                subsurface_depth: 0,
+                height_scale: 1.0,
+                blend: 0.3,
+            },
+            Biome::SnowyPeaks => &BiomeParams {
+                temperature: -0.7,
+                humidity: 0.5,
+                surface: "snow",
+                subsurface: "stone",
+                subsurface_depth: 1,
+                height_scale: 1.2,
+                blend: 0.3,
+            },
+            Biome::Desert => &BiomeParams {
+                temperature: 0.7,
+                humidity: -0.6,
+                surface: "sand",
+                subsurface: "sand",
+                subsurface_depth: 4,
+                height_scale: 0.25,
+                blend: 0.5,
+            },
+            Biome::Tundra => &BiomeParams {
+                temperature: -0.6,
+                humidity: -0.5,
+                surface: "snow",
+                subsurface: "dirt",
+                subsurface_depth: 2,
+                height_scale: 0.3,
+                blend: 0.4,
+            },
+        }
+    }
+}
+
+pub struct BiomeParams {
+    /// Climate this biome is found in, both in -1..1
+    pub temperature: f64,
+    pub humidity: f64,
+    /// Voxel covering the top of the terrain
+    pub surface: &'static str,
+    /// Voxel between the surface and stone
+    pub subsurface: &'static str,
+    pub subsurface_depth: u32,
+    /// Multiplier applied to the terrain noise
+    pub height_scale: f64,
+    /// How far into neighboring climates this biome's height reaches, in climate units
+    pub blend: f64,
+}
+
+/// Terrain shape and blocks of a single column, blended from nearby biomes
+pub struct ColumnBiome {
+    pub biome: Biome,
+    pub height_scale: f64,
+}
+
+/// Temperature and humidity noise maps deciding where each biome goes
+#[derive(Clone)]
+pub struct BiomeMap {
+    temperature: Fbm<OpenSimplex>,
+    humidity: Fbm<OpenSimplex>,
+}
+
+impl BiomeMap {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            temperature: Fbm::new(seed.wrapping_add(1)).set_octaves(4),
+            humidity: Fbm::new(seed.wrapping_add(2)).set_octaves(4),
+        }
+    }
+
+    pub fn climate_at(&self, x: i32, z: i32) -> (f64, f64) {
+        let point = [x as f64 / CLIMATE_SCALE, z as f64 / CLIMATE_SCALE];
+        (
+            self.temperature.get(point).clamp(-1.0, 1.0),
+            self.humidity.get(point).clamp(-1.0, 1.0),
+        )
+    }
+
+    /// Biome whose climate is closest to the one at this position
+    pub fn biome_at(&self, pos: GlobalVoxelPos) -> Biome {
+        self.column(pos.x, pos.z).biome
+    }
+
+    pub fn column(&self, x: i32, z: i32) -> ColumnBiome {
+        let (temperature, humidity) = self.climate_at(x, z);
+        let distances = Biome::ALL.map(|biome| {
+            let params = biome.params();
+            ((params.temperature - temperature).powi(2) + (params.humidity - humidity).powi(2))
+                .sqrt()
+        });
+
+        let (nearest, nearest_distance) = distances
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, distance)| (Biome::ALL[index], *distance))
+            .unwrap();
+
+        // Biomes nearly as close as the nearest one contribute to the height,
+        // so terrain slopes smoothly across borders instead of forming cliffs
+        let mut total_weight = 0.0;
+        let mut height_scale = 0.0;
+        for (biome, distance) in Biome::ALL.iter().zip(distances) {
+            let params = biome.params();
+            let weight = (1.0 - (distance - nearest_distance) / params.blend)
+                .max(0.0)
+                .powi(2);
+            total_weight += weight;
+            height_scale += weight * params.height_scale;
+        }
+
+        ColumnBiome {
+            biome: nearest,
+            height_scale: height_scale / total_weight,
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use noise::{Fbm, MultiFractal, NoiseFn, OpenSimplex};
+
+/// Worm tunnels run where both worm noises are close to zero
+const WORM_RADIUS: f64 = 0.06;
+const WORM_SCALE: f64 = 60.0;
+/// Caverns open up where the cavern noise is high
+const CAVERN_THRESHOLD: f64 = 0.55;
+const CAVERN_SCALE: f64 = 80.0;
+/// Caverns are flattened so they are wider than they are tall
+const CAVERN_SQUASH: f64 = 2.0;
+const OVERHANG_SCALE: f64 = 40.0;
+/// How many voxels the overhang noise can push the surface up or down
+const OVERHANG_AMPLITUDE: f64 = 12.0;
+
+/// 3D noise shaping the terrain beyond the heightmap.
+/// Every value only depends on the world seed and the global voxel position,
+/// so caves line up across chunk borders no matter the order chunks are generated in.
+#[derive(Clone)]
+pub struct CaveCarver {
+    worm_a: OpenSimplex,
+    worm_b: OpenSimplex,
+    caverns: Fbm<OpenSimplex>,
+    overhangs: Fbm<OpenSimplex>,
+}
+
+impl CaveCarver {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            worm_a: OpenSimplex::new(seed.wrapping_add(10)),
+            worm_b: OpenSimplex::new(seed.wrapping_add(11)),
+            caverns: Fbm::new(seed.wrapping_add(12)).set_octaves(3),
+            overhangs: Fbm::new(seed.wrapping_add(13)).set_octaves(3),
+        }
+    }
+
+    /// Whether a voxel is part of the terrain, given the surface height of its column.
+    /// Noise around the surface lets terrain lean past its column to form overhangs and arches.
+    pub fn is_solid(&self, x: i32, y: i32, z: i32, surface_height: f64) -> bool {
+        let depth = surface_height - y as f64;
+        if depth.abs() > OVERHANG_AMPLITUDE {
+            return depth > 0.0;
+        }
+
+        let point = [
+            x as f64 / OVERHANG_SCALE,
+            y as f64 / OVERHANG_SCALE,
+            z as f64 / OVERHANG_SCALE,
+        ];
+        depth + self.overhangs.get(point) * OVERHANG_AMPLITUDE > 0.0
+    }
+
+    /// Whether a solid voxel should be hollowed out by a worm cave or a cavern
+    pub fn is_carved(&self, x: i32, y: i32, z: i32) -> bool {
+        let (x, y, z) = (x as f64, y as f64, z as f64);
+
+        let worm = [x / WORM_SCALE, y / WORM_SCALE, z / WORM_SCALE];
+        if self.worm_a.get(worm).abs() < WORM_RADIUS && self.worm_b.get(worm).abs() < WORM_RADIUS {
+            return true;
+        }
+
+        let cavern = [
+            x / CAVERN_SCALE,
+            y * CAVERN_SQUASH / CAVERN_SCALE,
+            z / CAVERN_SCALE,
+        ];
+        self.caverns.get(cavern) > CAVERN_THRESHOLD
+    }
+}
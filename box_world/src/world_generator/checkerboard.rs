@@ -0,0 +1,49 @@
+use super::WorldGenerator;
+use crate::{
+    chunk::{ChunkData, ChunkPos},
+    voxel::{ChunkLocalVoxelPos, GlobalVoxelPos, VoxelRegistry},
+};
+
+const FLOOR_HEIGHT: i32 = 100;
+
+/// Debug world showing every registered voxel on a single floor,
+/// one voxel per cell with air between cells so each face can be inspected
+pub struct CheckerboardGenerator;
+
+impl WorldGenerator for CheckerboardGenerator {
+    fn generate(&self, pos: ChunkPos, voxel_registry: &VoxelRegistry) -> ChunkData {
+        let mut chunk = ChunkData::default();
+
+        let origin = GlobalVoxelPos::from_chunk_local(pos, ChunkLocalVoxelPos::new(0, 0, 0));
+        let Ok(y) = u32::try_from(FLOOR_HEIGHT - origin.y) else {
+            return chunk;
+        };
+        if y >= ChunkData::edge() {
+            return chunk;
+        }
+
+        let voxels: Vec<_> = voxel_registry
+            .voxels()
+            .into_iter()
+            .filter(|voxel| !voxel.is_air())
+            .collect();
+        if voxels.is_empty() {
+            return chunk;
+        }
+
+        for z in 0..ChunkData::edge() {
+            for x in 0..ChunkData::edge() {
+                let voxel_pos =
+                    GlobalVoxelPos::from_chunk_local(pos, ChunkLocalVoxelPos::new(x, y, z));
+                if voxel_pos.x.rem_euclid(2) != 0 || voxel_pos.z.rem_euclid(2) != 0 {
+                    continue;
+                }
+
+                let cell = voxel_pos.x.div_euclid(2) + voxel_pos.z.div_euclid(2);
+                let voxel = voxels[cell.rem_euclid(voxels.len() as i32) as usize];
+                chunk.set(x, y, z, voxel);
+            }
+        }
+        chunk
+    }
+}
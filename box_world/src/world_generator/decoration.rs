@@ -0,0 +1,202 @@
+use bevy::prelude::IVec3;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    chunk::{ChunkData, ChunkPos},
+    voxel::{ChunkLocalVoxelPos, GlobalVoxelPos, Voxel, VoxelRegistry},
+};
+
+const TREE_CHANCE: f64 = 1. / 80.;
+const BOULDER_CHANCE: f64 = 1. / 300.;
+const ORE_VEINS_PER_CHUNK: u32 = 4;
+
+/// Single voxel placed by a decoration, possibly outside the chunk that produced it
+#[derive(Clone, Copy, Debug)]
+pub struct VoxelWrite {
+    pub pos: GlobalVoxelPos,
+    pub voxel: Voxel,
+    /// The voxel is only placed over this one, air for surface structures
+    pub only_over: Voxel,
+}
+
+/// Structure made of voxels relative to an anchor
+pub struct VoxelTemplate {
+    voxels: Vec<(IVec3, Voxel)>,
+    only_over: Voxel,
+}
+
+impl VoxelTemplate {
+    /// Trunk rising from the anchor with a rounded crown of leaves
+    pub fn tree(height: i32, voxel_registry: &VoxelRegistry) -> Self {
+        let log = voxel_registry.get_voxel("log");
+        let leaves = voxel_registry.get_voxel("leaves");
+
+        let mut voxels: Vec<(IVec3, Voxel)> =
+            (0..height).map(|y| (IVec3::new(0, y, 0), log)).collect();
+        for y in height - 3..=height {
+            let radius = if y == height { 1 } else { 2 };
+            for z in -radius..=radius {
+                for x in -radius..=radius {
+                    // Trim the corners so the crown isn't a cube
+                    if x.abs() == radius && z.abs() == radius {
+                        continue;
+                    }
+                    if x == 0 && z == 0 && y < height {
+                        continue;
+                    }
+                    voxels.push((IVec3::new(x, y, z), leaves));
+                }
+            }
+        }
+
+        Self {
+            voxels,
+            only_over: voxel_registry.get_voxel("air"),
+        }
+    }
+
+    /// Rough ball of stone resting on the anchor
+    pub fn boulder(radius: i32, voxel_registry: &VoxelRegistry) -> Self {
+        let stone = voxel_registry.get_voxel("stone");
+
+        let mut voxels = Vec::new();
+        for y in 0..=radius * 2 {
+            for z in -radius..=radius {
+                for x in -radius..=radius {
+                    let offset = IVec3::new(x, y - radius, z);
+                    if offset.length_squared() <= radius * radius + 1 {
+                        voxels.push((IVec3::new(x, y, z), stone));
+                    }
+                }
+            }
+        }
+
+        Self {
+            voxels,
+            only_over: voxel_registry.get_voxel("air"),
+        }
+    }
+
+    /// Cluster of ore replacing stone, grown as a random walk from the anchor
+    pub fn ore_vein(size: u32, rng: &mut StdRng, voxel_registry: &VoxelRegistry) -> Self {
+        let ore = voxel_registry.get_voxel("coal_ore");
+
+        let mut voxels = Vec::new();
+        let mut current = IVec3::ZERO;
+        for _ in 0..size {
+            voxels.push((current, ore));
+            current += match rng.gen_range(0..6) {
+                0 => IVec3::X,
+                1 => IVec3::NEG_X,
+                2 => IVec3::Y,
+                3 => IVec3::NEG_Y,
+                4 => IVec3::Z,
+                _ => IVec3::NEG_Z,
+            };
+        }
+
+        Self {
+            voxels,
+            only_over: voxel_registry.get_voxel("stone"),
+        }
+    }
+
+    pub fn place(&self, anchor: GlobalVoxelPos, writes: &mut Vec<VoxelWrite>) {
+        writes.extend(self.voxels.iter().map(|(offset, voxel)| VoxelWrite {
+            pos: GlobalVoxelPos::new(
+                anchor.x + offset.x,
+                anchor.y + offset.y,
+                anchor.z + offset.z,
+            ),
+            voxel: *voxel,
+            only_over: self.only_over,
+        }));
+    }
+}
+
+/// Second generation phase placing trees, boulders and ore veins on top of base terrain
+pub struct Decorator {
+    seed: u32,
+}
+
+impl Decorator {
+    pub fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    /// Every chunk gets its own random stream so decorations don't depend on generation order
+    fn chunk_rng(&self, pos: ChunkPos) -> StdRng {
+        let hash = (self.seed as u64)
+            ^ (pos.x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (pos.y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ (pos.z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+        StdRng::seed_from_u64(hash)
+    }
+
+    /// `is_air_above` tells whether a voxel of the layer above the chunk is air,
+    /// so surfaces on the top layer of the chunk get decorated too
+    pub fn decorate(
+        &self,
+        pos: ChunkPos,
+        chunk: &ChunkData,
+        voxel_registry: &VoxelRegistry,
+        is_air_above: impl Fn(GlobalVoxelPos) -> bool,
+    ) -> Vec<VoxelWrite> {
+        let mut writes = Vec::new();
+        if chunk.is_empty() {
+            return writes;
+        }
+
+        let mut rng = self.chunk_rng(pos);
+        let grass = voxel_registry.get_voxel("grass");
+        let snow = voxel_registry.get_voxel("snow");
+        let stone = voxel_registry.get_voxel("stone");
+
+        for z in 0..ChunkData::edge() {
+            for x in 0..ChunkData::edge() {
+                for y in 0..ChunkData::edge() {
+                    let ground = chunk.get(x, y, z);
+                    if ground.is_air() {
+                        continue;
+                    }
+
+                    let anchor =
+                        GlobalVoxelPos::from_chunk_local(pos, ChunkLocalVoxelPos::new(x, y + 1, z));
+                    let air_above = if y + 1 < ChunkData::edge() {
+                        chunk.get(x, y + 1, z).is_air()
+                    } else {
+                        is_air_above(anchor)
+                    };
+                    if !air_above {
+                        continue;
+                    }
+
+                    if ground == grass && rng.gen_bool(TREE_CHANCE) {
+                        VoxelTemplate::tree(rng.gen_range(4..=6), voxel_registry)
+                            .place(anchor, &mut writes);
+                    } else if (ground == stone || ground == snow) && rng.gen_bool(BOULDER_CHANCE) {
+                        VoxelTemplate::boulder(rng.gen_range(1..=2), voxel_registry)
+                            .place(anchor, &mut writes);
+                    }
+                }
+            }
+        }
+
+        for _ in 0..ORE_VEINS_PER_CHUNK {
+            let (x, y, z) = (
+                rng.gen_range(0..ChunkData::edge()),
+                rng.gen_range(0..ChunkData::edge()),
+                rng.gen_range(0..ChunkData::edge()),
+            );
+            let size = rng.gen_range(4..=10);
+            if chunk.get(x, y, z) != stone {
+                continue;
+            }
+
+            let anchor = GlobalVoxelPos::from_chunk_local(pos, ChunkLocalVoxelPos::new(x, y, z));
+            VoxelTemplate::ore_vein(size, &mut rng, voxel_registry).place(anchor, &mut writes);
+        }
+
+        writes
+    }
+}
@@ -1,150 +1,248 @@
+use std::sync::Arc;
+
 use bevy::{
     prelude::*,
     tasks::{AsyncComputeTaskPool, Task},
+    utils::HashSet,
 };
 use futures_lite::future;
-use noise::{MultiFractal, NoiseFn, OpenSimplex, RidgedMulti};
-use std::io::Cursor;
-use zstd::stream::copy_decode;
 
 use crate::{
-    chunk::{ChunkData, ChunkPos, Database, LoadedChunks},
+    chunk::{
+        apply_to_lit_chunk, ChunkData, ChunkPos, ChunkPriorities, LoadedChunks, PendingWrite,
+        PendingWrites, VoxelAddedEvent, WorldMeta,
+    },
+    lighting::NeedsLightPass,
     mesher::NeedsMesh,
-    voxel::{ChunkLocalVoxelPos, GlobalVoxelPos, VoxelRegistry},
+    states::GameStates,
+    voxel::{GlobalVoxelPos, VoxelRegistry},
 };
 
+mod biome;
+mod caves;
+mod checkerboard;
+mod decoration;
+mod ridged;
+mod superflat;
+mod void;
+
+pub use biome::Biome;
+pub use checkerboard::CheckerboardGenerator;
+pub use decoration::VoxelWrite;
+pub use ridged::RidgedGenerator;
+pub use superflat::{LayerError, SuperflatGenerator};
+pub use void::VoidGenerator;
+
+/// Produces the initial content of chunks that were never saved
+pub trait WorldGenerator: Send + Sync {
+    fn generate(&self, pos: ChunkPos, voxel_registry: &VoxelRegistry) -> ChunkData;
+
+    /// Structures placed once the base terrain of the chunk is generated.
+    /// Writes outside the chunk are queued until their chunk generates or loads.
+    fn decorate(
+        &self,
+        _pos: ChunkPos,
+        _chunk: &ChunkData,
+        _voxel_registry: &VoxelRegistry,
+    ) -> Vec<VoxelWrite> {
+        Vec::new()
+    }
+
+    /// Biome at a position, for generators that have biomes
+    fn biome_at(&self, _pos: GlobalVoxelPos) -> Option<Biome> {
+        None
+    }
+}
+
+/// Generator used by the current world, picked from its metadata once voxels are loaded
+#[derive(Resource, Clone)]
+pub struct ActiveGenerator(pub Arc<dyn WorldGenerator>);
+
+impl FromWorld for ActiveGenerator {
+    fn from_world(world: &mut World) -> Self {
+        let world_meta = world
+            .get_resource::<WorldMeta>()
+            .expect("Failed to get WorldMeta");
+        let voxel_registry = world
+            .get_resource::<VoxelRegistry>()
+            .expect("Failed to get VoxelRegistry");
+
+        match generator_from_meta(world_meta, voxel_registry) {
+            Ok(generator) => Self(generator),
+            Err(err) => panic!("Invalid generator options for this world: {}", err),
+        }
+    }
+}
+
+/// Build the generator named in the world metadata, falling back to ridged terrain
+pub fn generator_from_meta(
+    world_meta: &WorldMeta,
+    voxel_registry: &VoxelRegistry,
+) -> Result<Arc<dyn WorldGenerator>, LayerError> {
+    Ok(match world_meta.generator.as_str() {
+        "ridged" => Arc::new(RidgedGenerator::new(world_meta.seed)),
+        "superflat" => Arc::new(SuperflatGenerator::new(
+            &world_meta.generator_options,
+            voxel_registry,
+        )?),
+        "void" => Arc::new(VoidGenerator),
+        "checkerboard" => Arc::new(CheckerboardGenerator),
+        other => {
+            warn!(
+                "Unknown world generator {}, using ridged terrain instead",
+                other
+            );
+            Arc::new(RidgedGenerator::new(world_meta.seed))
+        }
+    })
+}
+
+fn init_active_generator(mut commands: Commands) {
+    commands.init_resource::<ActiveGenerator>();
+}
+
 pub struct GeneratorPlugin;
 
 impl Plugin for GeneratorPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
+        // Generators resolve voxel names, so they wait for the voxel registry
+        app.add_systems(OnEnter(GameStates::WorldLoading), init_active_generator);
+
         app.add_systems(
             Update,
-            enqueue_chunk_generation_tasks.run_if(resource_exists::<VoxelRegistry>()),
+            enqueue_chunk_generation_tasks.run_if(resource_exists::<ActiveGenerator>()),
         );
 
         app.add_systems(PostUpdate, handle_done_generation_tasks);
     }
 }
 
+/// Chunk that was never saved, so its data has to be generated
 #[derive(Component)]
 #[component(storage = "SparseSet")]
-pub struct NeedsChunkData;
+pub struct NeedsGeneration;
 
 #[derive(Component)]
 #[component(storage = "SparseSet")]
-struct ComputeChunkData(Task<ChunkData>);
+struct ComputeChunkData(Task<GeneratedChunk>);
+
+struct GeneratedChunk {
+    data: ChunkData,
+    /// Decoration voxels that landed in other chunks
+    spilled: Vec<(ChunkPos, PendingWrite)>,
+}
 
 fn enqueue_chunk_generation_tasks(
     mut commands: Commands,
-    database: Res<Database>,
+    generator: Res<ActiveGenerator>,
     voxel_registry: Res<VoxelRegistry>,
-    needs_generation: Query<(Entity, &ChunkPos), With<NeedsChunkData>>,
+    priorities: Res<ChunkPriorities>,
+    needs_generation: Query<(Entity, &ChunkPos), With<NeedsGeneration>>,
 ) {
     if needs_generation.is_empty() {
         return;
     }
 
     let thread_pool = AsyncComputeTaskPool::get();
-    let noise: RidgedMulti<OpenSimplex> =
-        RidgedMulti::new(RidgedMulti::<OpenSimplex>::DEFAULT_SEED)
-            .set_octaves(8)
-            .set_frequency(0.25);
 
-    needs_generation
-        .iter()
-        .take(4096)
+    priorities
+        .most_urgent(
+            needs_generation
+                .iter()
+                .map(|(entity, pos)| (*pos, (entity, *pos))),
+            4096,
+        )
+        .into_iter()
         .for_each(|(entity, pos)| {
-            let pos = *pos;
-            let noise = noise.clone();
-            let connection_pool = database.get_connection_pool();
+            let generator = generator.0.clone();
             let voxel_registry = voxel_registry.clone();
 
             let task = thread_pool.spawn(async move {
                 let _span = info_span!("Generate a chunk").entered();
 
-                let connection = connection_pool.get().unwrap();
-                let stmt = connection.prepare(
-                    "SELECT posx, posy, posz, data FROM blocks WHERE posx=:posx AND posy=:posy AND posz=:posz;",
-                );
-                if let Ok(mut stmt) = stmt {
-                    let chunk_result: Result<Vec<u8>, _> = stmt.query_row(
-                        &[(":posx", &pos.x), (":posy", &pos.y), (":posz", &pos.z)],
-                        |row| Ok(row.get(3).unwrap()),
-                    );
-                    if let Ok(chunk_row) = chunk_result {
-                        let mut temp_output = Cursor::new(Vec::new());
-                        copy_decode(&chunk_row[..], &mut temp_output).unwrap();
-                        let final_chunk = bincode::deserialize(temp_output.get_ref()).unwrap();
-
-                        return ChunkData::from_raw(final_chunk);
+                let mut data = generator.generate(pos, &voxel_registry);
+                let mut spilled = Vec::new();
+                for write in generator.decorate(pos, &data, &voxel_registry) {
+                    let (chunk_pos, local_pos) = write.pos.to_chunk_local();
+                    let pending = PendingWrite::new(local_pos, write.voxel, write.only_over);
+                    if chunk_pos == pos {
+                        pending.apply(&mut data);
+                    } else {
+                        spilled.push((chunk_pos, pending));
                     }
                 }
 
-                let mut chunk = ChunkData::default();
-
-                for z in 0..ChunkData::edge() {
-                    for y in 0..ChunkData::edge() {
-                        for x in 0..ChunkData::edge() {
-                            let voxel_pos = GlobalVoxelPos::from_chunk_local(pos, ChunkLocalVoxelPos::new(x, y, z));
-                            let voxel = if voxel_pos.y <= 20 {
-                                if voxel_pos.y < 17 {
-                                    // Empty bottom chunk
-                                    voxel_registry.get_voxel("air")
-                                } else {
-                                    // Bedrock
-                                    voxel_registry.get_voxel("bedrock")
-                                }
-                            } else {
-                                let noise_val = noise
-                                    .get([voxel_pos.x as f64 / 100.0, voxel_pos.z as f64 / 100.0])
-                                    * 100.0;
-                                if (voxel_pos.y as f64) < 102. + noise_val {
-                                    // Stoney peaks
-                                    if voxel_pos.y > 150 {
-                                        voxel_registry.get_voxel("stone")
-                                    } else {
-                                        // Grass
-                                        voxel_registry.get_voxel("grass")
-                                    }
-                                } else if (voxel_pos.y as f64) < 100. + noise_val {
-                                    // Stone
-                                    voxel_registry.get_voxel("stone")
-                                } else {
-                                    // Air
-                                    voxel_registry.get_voxel("air")
-                                }
-                            };
-
-                            chunk.set(x, y, z, voxel);
-                        }
-                    }
-                }
-                chunk
+                GeneratedChunk { data, spilled }
             });
 
-            commands.entity(entity).remove::<NeedsChunkData>().insert(ComputeChunkData(task));
+            commands
+                .entity(entity)
+                .remove::<NeedsGeneration>()
+                .insert(ComputeChunkData(task));
         });
 }
 
 fn handle_done_generation_tasks(
     mut commands: Commands,
     world: Res<LoadedChunks>,
+    mut pending_writes: ResMut<PendingWrites>,
+    mut voxel_added: EventWriter<VoxelAddedEvent>,
     mut generation_tasks: Query<(Entity, &ChunkPos, &mut ComputeChunkData)>,
+    mut chunks: Query<&mut ChunkData>,
 ) {
-    let mut loaded = Vec::new();
+    let mut done = Vec::new();
     generation_tasks
         .iter_mut()
         .take(4096)
         .for_each(|(task_entity, pos, mut task)| {
-            if let Some(data) = future::block_on(future::poll_once(&mut task.0)) {
-                commands
-                    .entity(task_entity)
-                    .remove::<ComputeChunkData>()
-                    .insert(data);
-                loaded.push(*pos);
+            if let Some(generated) = future::block_on(future::poll_once(&mut task.0)) {
+                done.push((task_entity, *pos, generated));
             }
         });
 
+    // Queue spilled decorations first so chunks finishing this frame pick them up
+    let mut spilled_into = HashSet::new();
+    for (_entity, _pos, generated) in done.iter_mut() {
+        for (pos, write) in generated.spilled.drain(..) {
+            pending_writes.push(pos, write);
+            spilled_into.insert(pos);
+        }
+    }
+
+    let mut loaded = Vec::new();
+    for (task_entity, pos, generated) in done {
+        let mut data = generated.data;
+
+        // Freshly generated chunks have no lighting yet, so writes can go in directly
+        if let Some(writes) = pending_writes.take(pos) {
+            writes.iter().for_each(|write| {
+                write.apply(&mut data);
+            });
+        }
+
+        commands
+            .entity(task_entity)
+            .remove::<ComputeChunkData>()
+            .insert((data, NeedsLightPass));
+        spilled_into.remove(&pos);
+        loaded.push(pos);
+    }
+
+    // Chunks already in the world get their decorations right away,
+    // those still generating will find them in the queue when they finish
+    for pos in spilled_into {
+        let Some(entity) = world.get_chunk(pos) else {
+            continue;
+        };
+        let Ok(mut data) = chunks.get_mut(*entity) else {
+            continue;
+        };
+        if let Some(writes) = pending_writes.take(pos) {
+            voxel_added.send_batch(apply_to_lit_chunk(pos, &mut data, &writes));
+            loaded.push(pos);
+        }
+    }
+
     // Re-mesh all neighbors after loading new chunks to simplify geometry
     for neighbor in world.get_unique_loaded_chunks_and_neighbors(&loaded) {
         commands.entity(neighbor).insert(NeedsMesh);
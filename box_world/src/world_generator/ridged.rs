@@ -0,0 +1,136 @@
+use noise::{MultiFractal, NoiseFn, OpenSimplex, RidgedMulti};
+
+use super::{
+    biome::{Biome, BiomeMap, ColumnBiome},
+    caves::CaveCarver,
+    decoration::{Decorator, VoxelWrite},
+    WorldGenerator,
+};
+use crate::{
+    chunk::{ChunkData, ChunkPos},
+    voxel::{ChunkLocalVoxelPos, GlobalVoxelPos, VoxelRegistry},
+};
+
+/// Caves stop a few voxels above the bedrock so the floor of the world stays sealed
+const CAVE_FLOOR: i32 = 24;
+
+/// Ridged terrain shaped and covered by biomes, above a bedrock floor
+pub struct RidgedGenerator {
+    noise: RidgedMulti<OpenSimplex>,
+    biomes: BiomeMap,
+    caves: CaveCarver,
+    decorator: Decorator,
+}
+
+impl RidgedGenerator {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            noise: RidgedMulti::new(seed).set_octaves(8).set_frequency(0.25),
+            biomes: BiomeMap::new(seed),
+            caves: CaveCarver::new(seed),
+            decorator: Decorator::new(seed),
+        }
+    }
+}
+
+impl RidgedGenerator {
+    /// Biome column and terrain height at a horizontal position
+    fn column(&self, x: i32, z: i32) -> (ColumnBiome, f64) {
+        let column = self.biomes.column(x, z);
+        let noise_val =
+            self.noise.get([x as f64 / 100.0, z as f64 / 100.0]) * 100.0 * column.height_scale;
+        (column, 102. + noise_val)
+    }
+
+    /// Whether a single voxel of the base terrain is air, without generating its chunk
+    fn is_air(&self, pos: GlobalVoxelPos) -> bool {
+        if pos.y <= 20 {
+            return pos.y < 17;
+        }
+
+        let (_column, height) = self.column(pos.x, pos.z);
+        !self.caves.is_solid(pos.x, pos.y, pos.z, height)
+            || (pos.y > CAVE_FLOOR && self.caves.is_carved(pos.x, pos.y, pos.z))
+    }
+}
+
+impl WorldGenerator for RidgedGenerator {
+    fn generate(&self, pos: ChunkPos, voxel_registry: &VoxelRegistry) -> ChunkData {
+        let mut chunk = ChunkData::default();
+
+        let air = voxel_registry.get_voxel("air");
+        let bedrock = voxel_registry.get_voxel("bedrock");
+        let stone = voxel_registry.get_voxel("stone");
+
+        for z in 0..ChunkData::edge() {
+            for x in 0..ChunkData::edge() {
+                let column_pos =
+                    GlobalVoxelPos::from_chunk_local(pos, ChunkLocalVoxelPos::new(x, 0, z));
+                let (column, height) = self.column(column_pos.x, column_pos.z);
+                let params = column.biome.params();
+                let surface = voxel_registry.get_voxel(params.surface);
+                let subsurface = voxel_registry.get_voxel(params.subsurface);
+
+                // Look past the top of the chunk so surface layers continue across chunk borders
+                let lookahead = params.subsurface_depth as usize + 1;
+                let solid: Vec<bool> = (0..ChunkData::edge() as usize + lookahead)
+                    .map(|y| {
+                        let voxel_y = column_pos.y + y as i32;
+                        voxel_y > 20
+                            && self
+                                .caves
+                                .is_solid(column_pos.x, voxel_y, column_pos.z, height)
+                    })
+                    .collect();
+
+                for y in 0..ChunkData::edge() {
+                    let voxel_y = column_pos.y + y as i32;
+                    let voxel = if voxel_y <= 20 {
+                        if voxel_y < 17 {
+                            // Empty bottom chunk
+                            air
+                        } else {
+                            bedrock
+                        }
+                    } else if !solid[y as usize] {
+                        air
+                    } else if voxel_y > CAVE_FLOOR
+                        && self.caves.is_carved(column_pos.x, voxel_y, column_pos.z)
+                    {
+                        air
+                    } else {
+                        let depth = solid[y as usize + 1..]
+                            .iter()
+                            .take(lookahead)
+                            .take_while(|solid| **solid)
+                            .count() as u32;
+                        if depth < 1 {
+                            surface
+                        } else if depth <= params.subsurface_depth {
+                            subsurface
+                        } else {
+                            stone
+                        }
+                    };
+
+                    chunk.set(x, y, z, voxel);
+                }
+            }
+        }
+        chunk
+    }
+
+    fn decorate(
+        &self,
+        pos: ChunkPos,
+        chunk: &ChunkData,
+        voxel_registry: &VoxelRegistry,
+    ) -> Vec<VoxelWrite> {
+        self.decorator
+            .decorate(pos, chunk, voxel_registry, |above| self.is_air(above))
+    }
+
+    fn biome_at(&self, pos: GlobalVoxelPos) -> Option<Biome> {
+        Some(self.biomes.biome_at(pos))
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+
+use super::WorldGenerator;
+use crate::{
+    chunk::{ChunkData, ChunkPos},
+    voxel::{ChunkLocalVoxelPos, GlobalVoxelPos, Voxel, VoxelRegistry},
+};
+
+pub const DEFAULT_LAYERS: &str = "bedrock,stone*3,dirt*2,grass";
+
+/// Why a superflat layer list can't be used
+#[derive(Debug, PartialEq, Eq)]
+pub enum LayerError {
+    /// Voxel name without a definition
+    UnknownVoxel(String),
+    /// Repeat count that isn't a positive number
+    InvalidCount(String),
+}
+
+impl fmt::Display for LayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayerError::UnknownVoxel(name) => write!(f, "unknown voxel {:?} in layers", name),
+            LayerError::InvalidCount(count) => write!(f, "invalid layer count {:?}", count),
+        }
+    }
+}
+
+impl std::error::Error for LayerError {}
+
+/// Flat world made of horizontal layers stacked from y 0 upwards
+pub struct SuperflatGenerator {
+    /// Voxel of each layer, bottom to top, one entry per voxel of height
+    layers: Vec<Voxel>,
+}
+
+impl SuperflatGenerator {
+    /// Layers are listed bottom to top as comma separated voxel names,
+    /// with an optional `*count` to repeat a layer, such as `bedrock,dirt*3,grass`
+    pub fn new(layers: &str, voxel_registry: &VoxelRegistry) -> Result<Self, LayerError> {
+        let mut voxels = Vec::new();
+        for (name, count) in parse_layers(layers)? {
+            let voxel = voxel_registry
+                .find_voxel(name)
+                .ok_or_else(|| LayerError::UnknownVoxel(name.to_string()))?;
+            voxels.extend(std::iter::repeat(voxel).take(count));
+        }
+
+        Ok(Self { layers: voxels })
+    }
+}
+
+/// Name and repeat count of each layer, bottom to top
+fn parse_layers(layers: &str) -> Result<Vec<(&str, usize)>, LayerError> {
+    let layers = if layers.trim().is_empty() {
+        DEFAULT_LAYERS
+    } else {
+        layers
+    };
+
+    layers
+        .split(',')
+        .map(|layer| {
+            let (name, count) = match layer.split_once('*') {
+                Some((name, count)) => {
+                    let count = count
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|count| *count > 0)
+                        .ok_or_else(|| LayerError::InvalidCount(count.trim().to_string()))?;
+                    (name, count)
+                }
+                None => (layer, 1),
+            };
+            Ok((name.trim(), count))
+        })
+        .collect()
+}
+
+impl WorldGenerator for SuperflatGenerator {
+    fn generate(&self, pos: ChunkPos, _voxel_registry: &VoxelRegistry) -> ChunkData {
+        let mut chunk = ChunkData::default();
+
+        for y in 0..ChunkData::edge() {
+            let voxel_pos = GlobalVoxelPos::from_chunk_local(pos, ChunkLocalVoxelPos::new(0, y, 0));
+            let Ok(layer) = usize::try_from(voxel_pos.y) else {
+                continue;
+            };
+            let Some(voxel) = self.layers.get(layer) else {
+                continue;
+            };
+
+            for z in 0..ChunkData::edge() {
+                for x in 0..ChunkData::edge() {
+                    chunk.set(x, y, z, *voxel);
+                }
+            }
+        }
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_counts() {
+        assert_eq!(
+            parse_layers("bedrock, dirt*3 ,grass"),
+            Ok(vec![("bedrock", 1), ("dirt", 3), ("grass", 1)])
+        );
+        assert_eq!(parse_layers("").unwrap().len(), 4);
+    }
+
+    #[test]
+    fn rejects_bad_counts() {
+        assert_eq!(
+            parse_layers("dirt*three"),
+            Err(LayerError::InvalidCount("three".to_string()))
+        );
+        assert_eq!(
+            parse_layers("dirt*0"),
+            Err(LayerError::InvalidCount("0".to_string()))
+        );
+    }
+}
@@ -0,0 +1,14 @@
+use super::WorldGenerator;
+use crate::{
+    chunk::{ChunkData, ChunkPos},
+    voxel::VoxelRegistry,
+};
+
+/// Nothing but air
+pub struct VoidGenerator;
+
+impl WorldGenerator for VoidGenerator {
+    fn generate(&self, _pos: ChunkPos, _voxel_registry: &VoxelRegistry) -> ChunkData {
+        ChunkData::default()
+    }
+}
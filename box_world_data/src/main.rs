@@ -1,4 +1,8 @@
-use std::{collections::HashMap, fs, process::Command};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    process::Command,
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -9,26 +13,102 @@ enum VoxelType {
     Transparent,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+/// Texture names of the faces of a block, without the png extension
+#[derive(Debug, Deserialize, Clone)]
+enum BlockTextures {
+    /// Same texture on every face
+    All(String),
+    TopBottomSide {
+        top: String,
+        bottom: String,
+        side: String,
+    },
+    /// X-, X+, Y-, Y+, Z-, Z+, in the face order of the mesher
+    Faces([String; 6]),
+}
+
+impl BlockTextures {
+    fn faces(&self) -> [&str; 6] {
+        match self {
+            BlockTextures::All(name) => [name.as_str(); 6],
+            BlockTextures::TopBottomSide { top, bottom, side } => {
+                [side, side, bottom, top, side, side].map(String::as_str)
+            }
+            BlockTextures::Faces(names) => names.each_ref().map(String::as_str),
+        }
+    }
+}
+
+/// Box inside a voxel, in voxel units from 0.0 to 1.0 on each axis
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+struct ShapeBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+enum BlockShape {
+    #[default]
+    Cube,
+    Slab,
+    Stair,
+    Cross,
+    Boxes(Vec<ShapeBox>),
+}
+
+/// Property of a block that changes from one placed voxel to another
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+enum StateProperty {
+    Facing,
+    Axis,
+    Waterlogged,
+}
+
+#[derive(Debug, Deserialize, Clone)]
 struct VoxelData {
     pub voxel_type: VoxelType,
-    pub texture_id: u32,
+    /// Defaults to the texture named after the block on every face
+    pub textures: Option<BlockTextures>,
     pub emissiveness: Option<u8>,
+    /// Defaults to a full cube
+    pub shape: Option<BlockShape>,
+    /// Defaults to no properties, every voxel of the block is the same
+    pub properties: Option<Vec<StateProperty>>,
+}
+
+impl VoxelData {
+    fn face_textures<'a>(&'a self, voxel_name: &'a str) -> [&'a str; 6] {
+        match &self.textures {
+            Some(textures) => textures.faces(),
+            None => [voxel_name; 6],
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Clone, Copy)]
+#[derive(Debug, Serialize, Clone)]
 struct FinalVoxelType {
     pub visibility: VoxelType,
-    pub texture_id: u16,
+    /// Index in the texture array of each face
+    pub texture_ids: [u16; 6],
     pub emissiveness: u8,
+    pub shape: BlockShape,
+    pub properties: Vec<StateProperty>,
 }
 
 impl FinalVoxelType {
-    pub fn from_voxel_data(data: VoxelData) -> Self {
+    pub fn from_voxel_data(voxel_name: &str, data: &VoxelData, textures: &[String]) -> Self {
         Self {
             visibility: data.voxel_type,
-            texture_id: data.texture_id.try_into().unwrap(),
+            texture_ids: data.face_textures(voxel_name).map(|texture| {
+                let index = textures
+                    .iter()
+                    .position(|name| name == texture)
+                    .unwrap();
+                index.try_into().unwrap()
+            }),
             emissiveness: data.emissiveness.unwrap_or(0),
+            shape: data.shape.clone().unwrap_or_default(),
+            properties: data.properties.clone().unwrap_or_default(),
         }
     }
 }
@@ -49,14 +129,24 @@ fn main() {
         blocks.insert(voxel_name, voxel_data);
     }
 
-    generate_final_voxel_data(&blocks);
-    generate_texture_list(&blocks);
+    let textures = texture_names(&blocks);
+    generate_final_voxel_data(&blocks, &textures);
+    generate_texture_list(&textures);
     generate_texture_array();
 }
 
-fn generate_final_voxel_data(blocks: &HashMap<String, VoxelData>) {
+/// Every texture used by a block, sorted by name and listed once even when shared
+fn texture_names(blocks: &HashMap<String, VoxelData>) -> Vec<String> {
+    let names: BTreeSet<&str> = blocks
+        .iter()
+        .flat_map(|(voxel_name, voxel_data)| voxel_data.face_textures(voxel_name))
+        .collect();
+    names.into_iter().map(str::to_string).collect()
+}
+
+fn generate_final_voxel_data(blocks: &HashMap<String, VoxelData>, textures: &[String]) {
     for (voxel_name, voxel_data) in blocks.iter() {
-        let final_data = FinalVoxelType::from_voxel_data(*voxel_data);
+        let final_data = FinalVoxelType::from_voxel_data(voxel_name, voxel_data, textures);
         let mut final_string = ron::to_string(&final_data).unwrap();
         if cfg!(windows) {
             final_string.push_str("\r\n");
@@ -69,13 +159,10 @@ fn generate_final_voxel_data(blocks: &HashMap<String, VoxelData>) {
     }
 }
 
-fn generate_texture_list(blocks: &HashMap<String, VoxelData>) {
-    let mut blocks: Vec<_> = blocks.iter().collect();
-    blocks.sort_by_key(|(_name, data)| data.texture_id);
-
+fn generate_texture_list(textures: &[String]) {
     let mut content = String::new();
-    for (voxel_name, _voxel_data) in blocks.into_iter() {
-        content.push_str(voxel_name);
+    for texture in textures.iter() {
+        content.push_str(texture);
         content.push_str(".png");
         if cfg!(windows) {
             content.push_str("\r\n");